@@ -11,7 +11,7 @@ fn main() {
     all(unix, not(target_os = "macos"), not(target_os = "ios"), not(target_os = "android"))
 ))]
 fn main() {
-    use chrono::{DateTime, Local, NaiveDateTime, Utc};
+    use chrono::{DateTime, Local, Utc};
     let trash_items = trash::os_limited::list().unwrap();
 
     let now = Local::now();
@@ -19,8 +19,7 @@ fn main() {
     let old_count = trash_items
         .iter()
         .filter(|item| {
-            let naive_deletion_utc = NaiveDateTime::from_timestamp(item.time_deleted, 0);
-            let deletion = DateTime::<Utc>::from_utc(naive_deletion_utc, Utc);
+            let deletion = DateTime::<Utc>::from_timestamp(item.time_deleted, 0).unwrap();
             deletion < long_time_ago
         })
         .count();
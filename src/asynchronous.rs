@@ -0,0 +1,208 @@
+//! Asynchronous versions of the trash operations, for use within a [tokio](https://tokio.rs) runtime.
+//!
+//! Every operation is performed on the blocking thread pool of tokio. Operations on multiple items
+//! process one item at a time and await each of them, so dropping the returned future stops the
+//! operation after the item that is currently being processed. Items processed up until then
+//! stay where they were moved to.
+//!
+//! Every function here is a convenience wrapper around the method of the same name with an
+//! `_async` suffix on [`DEFAULT_TRASH_CTX`].
+//!
+//! # Example
+//!
+//! ```
+//! # #[tokio::main(flavor = "current_thread")]
+//! # async fn main() {
+//! use std::fs::File;
+//! File::create_new("delete_me_async").unwrap();
+//! trash::asynchronous::delete_all_with_info(["delete_me_async"]).await.unwrap();
+//! assert!(File::open("delete_me_async").is_err());
+//! # }
+//! ```
+
+use std::path::{Path, PathBuf};
+
+use tokio::task::spawn_blocking;
+
+use crate::{canonicalize_paths, into_unknown, Error, TrashContext, TrashItem, DEFAULT_TRASH_CTX};
+
+/// Runs `f` on the blocking thread pool of tokio.
+async fn blocking<F, R>(f: F) -> Result<R, Error>
+where
+    F: FnOnce() -> Result<R, Error> + Send + 'static,
+    R: Send + 'static,
+{
+    spawn_blocking(f).await.map_err(into_unknown)?
+}
+
+impl TrashContext {
+    /// Same as [`delete_all_with_info`](TrashContext::delete_all_with_info), but moves one item
+    /// at a time without blocking the runtime.
+    ///
+    /// All paths are canonicalized before any item is moved.
+    pub async fn delete_all_with_info_async<I, T>(&self, paths: I) -> Result<Option<Vec<TrashItem>>, Error>
+    where
+        I: IntoIterator<Item = T>,
+        T: AsRef<Path>,
+    {
+        let paths: Vec<PathBuf> = paths.into_iter().map(|path| path.as_ref().to_owned()).collect();
+        let full_paths = blocking(move || canonicalize_paths(paths)).await?;
+        let mut result: Option<Vec<TrashItem>> = None;
+        for full_path in full_paths {
+            let ctx = self.clone();
+            if let Some(items) = blocking(move || ctx.delete_canonicalized(vec![full_path], true)).await? {
+                result.get_or_insert_with(Vec::new).extend(items);
+            }
+        }
+        Ok(result)
+    }
+}
+
+/// Convenience method for `DEFAULT_TRASH_CTX.delete_all_with_info_async()`.
+///
+/// See: [`TrashContext::delete_all_with_info_async`](TrashContext::delete_all_with_info_async)
+pub async fn delete_all_with_info<I, T>(paths: I) -> Result<Option<Vec<TrashItem>>, Error>
+where
+    I: IntoIterator<Item = T>,
+    T: AsRef<Path>,
+{
+    DEFAULT_TRASH_CTX.delete_all_with_info_async(paths).await
+}
+
+#[cfg(any(
+    target_os = "windows",
+    all(unix, not(target_os = "macos"), not(target_os = "ios"), not(target_os = "android"))
+))]
+mod os_limited {
+    use std::borrow::Borrow;
+
+    use super::blocking;
+    use crate::{os_limited::check_twins, Error, TrashContext, TrashItem, DEFAULT_TRASH_CTX};
+
+    impl TrashContext {
+        /// Same as [`list`](TrashContext::list), but without blocking the runtime.
+        pub async fn list_async(&self) -> Result<Vec<TrashItem>, Error> {
+            let ctx = self.clone();
+            blocking(move || ctx.list()).await
+        }
+
+        /// Same as [`purge_all`](TrashContext::purge_all), but purges one item at a time without
+        /// blocking the runtime.
+        pub async fn purge_all_async<I>(&self, items: I) -> Result<(), Error>
+        where
+            I: IntoIterator,
+            <I as IntoIterator>::Item: Borrow<TrashItem>,
+        {
+            let items: Vec<TrashItem> = items.into_iter().map(|item| item.borrow().clone()).collect();
+            for item in items {
+                let ctx = self.clone();
+                blocking(move || ctx.purge_all([item])).await?;
+            }
+            Ok(())
+        }
+
+        /// Same as [`restore_all`](TrashContext::restore_all), but restores one item at a time
+        /// without blocking the runtime.
+        ///
+        /// The items are checked for twins before any item is restored.
+        pub async fn restore_all_async<I>(&self, items: I) -> Result<(), Error>
+        where
+            I: IntoIterator<Item = TrashItem>,
+        {
+            let mut iter = check_twins(items.into_iter().collect())?.into_iter();
+            while let Some(item) = iter.next() {
+                let ctx = self.clone();
+                match blocking(move || ctx.restore_all([item])).await {
+                    Err(Error::RestoreCollision { path, mut remaining_items }) => {
+                        remaining_items.extend(iter);
+                        return Err(Error::RestoreCollision { path, remaining_items });
+                    }
+                    result => result?,
+                }
+            }
+            Ok(())
+        }
+    }
+
+    /// Convenience method for `DEFAULT_TRASH_CTX.list_async()`.
+    ///
+    /// See: [`TrashContext::list_async`](TrashContext::list_async)
+    pub async fn list() -> Result<Vec<TrashItem>, Error> {
+        DEFAULT_TRASH_CTX.list_async().await
+    }
+
+    /// Convenience method for `DEFAULT_TRASH_CTX.purge_all_async()`.
+    ///
+    /// See: [`TrashContext::purge_all_async`](TrashContext::purge_all_async)
+    pub async fn purge_all<I>(items: I) -> Result<(), Error>
+    where
+        I: IntoIterator,
+        <I as IntoIterator>::Item: Borrow<TrashItem>,
+    {
+        DEFAULT_TRASH_CTX.purge_all_async(items).await
+    }
+
+    /// Convenience method for `DEFAULT_TRASH_CTX.restore_all_async()`.
+    ///
+    /// See: [`TrashContext::restore_all_async`](TrashContext::restore_all_async)
+    pub async fn restore_all<I>(items: I) -> Result<(), Error>
+    where
+        I: IntoIterator<Item = TrashItem>,
+    {
+        DEFAULT_TRASH_CTX.restore_all_async(items).await
+    }
+}
+#[cfg(any(
+    target_os = "windows",
+    all(unix, not(target_os = "macos"), not(target_os = "ios"), not(target_os = "android"))
+))]
+pub use os_limited::{list, purge_all, restore_all};
+
+#[cfg(test)]
+mod tests {
+    use crate::{backend::MemoryBackend, tests::get_unique_name, TrashContext};
+
+    #[tokio::test]
+    async fn delete_all_with_info() {
+        let backend = MemoryBackend::new();
+        let ctx = TrashContext::with_backend(backend.clone());
+        let names = [get_unique_name(), get_unique_name()];
+
+        let items = ctx.delete_all_with_info_async(&names).await.unwrap().unwrap();
+        assert_eq!(items.len(), 2);
+        assert_eq!(backend.items(), items);
+    }
+
+    #[cfg(any(
+        target_os = "windows",
+        all(unix, not(target_os = "macos"), not(target_os = "ios"), not(target_os = "android"))
+    ))]
+    #[tokio::test]
+    async fn purge_and_restore() {
+        let backend = MemoryBackend::new();
+        let ctx = TrashContext::with_backend(backend.clone());
+        ctx.delete_all([get_unique_name(), get_unique_name(), get_unique_name()]).unwrap();
+
+        let items = ctx.list_async().await.unwrap();
+        assert_eq!(items.len(), 3);
+        ctx.purge_all_async(&items[..1]).await.unwrap();
+        ctx.restore_all_async(items[1..].to_vec()).await.unwrap();
+        assert!(backend.items().is_empty());
+    }
+
+    #[cfg(any(
+        target_os = "windows",
+        all(unix, not(target_os = "macos"), not(target_os = "ios"), not(target_os = "android"))
+    ))]
+    #[tokio::test]
+    async fn restore_twins_restores_nothing() {
+        let backend = MemoryBackend::new();
+        let ctx = TrashContext::with_backend(backend.clone());
+        let name = get_unique_name();
+        ctx.delete_all([&name, &name]).unwrap();
+
+        let result = ctx.restore_all_async(backend.items()).await;
+        assert!(matches!(result, Err(crate::Error::RestoreTwins { .. })));
+        assert_eq!(backend.items().len(), 2);
+    }
+}
@@ -0,0 +1,372 @@
+//! Backends allow replacing the trash of the operating system with a different implementation.
+//!
+//! A [`TrashContext`](crate::TrashContext) created with
+//! [`TrashContext::with_backend`](crate::TrashContext::with_backend) performs every operation
+//! through its backend. The [`MemoryBackend`] is useful for testing code that moves items to the
+//! trash without touching the trash of the user.
+
+use std::{
+    collections::{HashMap, HashSet},
+    ffi::OsString,
+    fmt, fs,
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use crate::{Error, ExistingKind, RestoreStatus, TrashItem, TrashItemMetadata, TrashItemSize};
+
+/// The operations a trash implementation has to provide.
+///
+/// Paths handed to [`delete_all`](TrashBackend::delete_all) are canonicalized already, and
+/// items handed to [`restore_all`](TrashBackend::restore_all) are checked for twins beforehand.
+pub trait TrashBackend: fmt::Debug + Send + Sync {
+    /// Moves all `full_paths` to the trash, returning the created items if `with_info` is set.
+    fn delete_all(&self, full_paths: Vec<PathBuf>, with_info: bool) -> Result<Option<Vec<TrashItem>>, Error>;
+
+    /// Returns all items that are currently in the trash.
+    fn list(&self) -> Result<Vec<TrashItem>, Error>;
+
+    /// Returns whether the trash is empty or has at least one item.
+    fn is_empty(&self) -> Result<bool, Error> {
+        Ok(self.list()?.is_empty())
+    }
+
+    /// Returns the folders that make up the trash, if there are any.
+    fn trash_folders(&self) -> Result<HashSet<PathBuf>, Error> {
+        Ok(HashSet::new())
+    }
+
+    /// Returns the [`TrashItemMetadata`] for an item in the trash.
+    fn metadata(&self, item: &TrashItem) -> Result<TrashItemMetadata, Error>;
+
+    /// Returns the [`TrashItemMetadata`] for an item in the trash, including its
+    /// [`total_size`](TrashItemMetadata::total_size) and [`inode_count`](TrashItemMetadata::inode_count).
+    ///
+    /// The default implementation returns the [`metadata`](TrashBackend::metadata) of the item.
+    fn deep_metadata(&self, item: &TrashItem) -> Result<TrashItemMetadata, Error> {
+        self.metadata(item)
+    }
+
+    /// Returns what restoring `item` to its original path would run into, not considering any
+    /// other items. This is used to plan restoring and to resolve conflicts ahead of restoring.
+    ///
+    /// The default implementation returns `None`, which means that the file system is checked,
+    /// as is right for backends that restore items to the file system.
+    fn restore_status(&self, _item: &TrashItem) -> Option<RestoreStatus> {
+        None
+    }
+
+    /// Deletes all the provided items permanently.
+    fn purge_all(&self, items: Vec<TrashItem>) -> Result<(), Error>;
+
+    /// Restores all the provided items to their original location.
+    ///
+    /// Like the trash of the operating system, an item whose original path is taken should
+    /// result in a [`RestoreCollision`](Error::RestoreCollision) kind of error.
+    fn restore_all(&self, items: Vec<TrashItem>) -> Result<(), Error>;
+}
+
+/// The error for operations that only the trash of the operating system supports.
+#[cfg(all(unix, not(target_os = "macos"), not(target_os = "ios"), not(target_os = "android")))]
+pub(crate) fn unsupported(operation: &str) -> Error {
+    Error::Unknown { description: format!("`{operation}` is not supported by trash backends") }
+}
+
+/// A trash that only exists in memory.
+///
+/// Deleting records a [`TrashItem`] for every path, but nothing is moved on the file system,
+/// neither when deleting, nor when purging or restoring. The metadata of an item is read from
+/// the file system at the time of deletion, if the path exists.
+///
+/// Instead of looking at the file system, the backend keeps track of the paths that are taken.
+/// Restoring an item takes its original path and deleting a path frees it, so restoring the same
+/// item twice collides like it would on the file system. Other paths can be taken with
+/// [`add_existing`](MemoryBackend::add_existing).
+///
+/// Clones share the same items, so a clone can be handed to a [`TrashContext`](crate::TrashContext)
+/// while the original is used to inspect the trash.
+#[derive(Clone, Debug, Default)]
+pub struct MemoryBackend {
+    state: Arc<Mutex<MemoryState>>,
+}
+
+#[derive(Debug, Default)]
+struct MemoryState {
+    next_id: u64,
+    items: Vec<(TrashItem, TrashItemMetadata)>,
+    existing: HashMap<PathBuf, ExistingKind>,
+}
+
+impl MemoryBackend {
+    /// Creates an empty trash.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Returns all items that are currently in the trash, in the order they were deleted.
+    pub fn items(&self) -> Vec<TrashItem> {
+        self.state.lock().unwrap().items.iter().map(|(item, _)| item.clone()).collect()
+    }
+
+    /// Removes all items from the trash.
+    pub fn clear(&self) {
+        self.state.lock().unwrap().items.clear();
+    }
+
+    /// Takes `path` by an item of the given `kind`, so that restoring an item to it collides.
+    pub fn add_existing(&self, path: impl Into<PathBuf>, kind: ExistingKind) {
+        self.state.lock().unwrap().existing.insert(path.into(), kind);
+    }
+
+    /// Returns the kind of the item that takes `path`, if it's taken.
+    pub fn existing(&self, path: impl AsRef<Path>) -> Option<ExistingKind> {
+        self.state.lock().unwrap().existing.get(path.as_ref()).copied()
+    }
+}
+
+impl MemoryState {
+    /// Makes sure every item is present, so that nothing is changed if one of them is missing.
+    fn check_present(&self, items: &[TrashItem]) -> Result<(), Error> {
+        match items.iter().find(|item| !self.items.iter().any(|(known, _)| known == *item)) {
+            Some(missing) => Err(not_in_trash(missing)),
+            None => Ok(()),
+        }
+    }
+
+    fn remove(&mut self, item: &TrashItem) -> TrashItemMetadata {
+        let index = self.items.iter().position(|(known, _)| known == item).expect("the item should be present");
+        self.items.remove(index).1
+    }
+}
+
+impl TrashBackend for MemoryBackend {
+    fn delete_all(&self, full_paths: Vec<PathBuf>, with_info: bool) -> Result<Option<Vec<TrashItem>>, Error> {
+        let time_deleted = SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs() as i64).unwrap_or(-1);
+        // Every path is checked first, so that nothing is recorded if one of them is invalid
+        let targets = full_paths
+            .into_iter()
+            .map(|path| match (path.file_name(), path.parent()) {
+                (Some(name), Some(parent)) => Ok((name.to_owned(), parent.to_owned(), path)),
+                _ => Err(Error::TargetedRoot),
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        let mut state = self.state.lock().unwrap();
+        let mut items = Vec::with_capacity(targets.len());
+        for (name, original_parent, path) in targets {
+            let mut metadata = match fs::symlink_metadata(&path) {
+                Ok(metadata) if metadata.is_dir() => {
+                    let entries = fs::read_dir(&path).map(|entries| entries.count()).unwrap_or(0);
+                    TrashItemMetadata::from_fs(&path, &metadata, TrashItemSize::Entries(entries))
+                }
+                Ok(metadata) => TrashItemMetadata::from_fs(&path, &metadata, TrashItemSize::Bytes(metadata.len())),
+                Err(_) => TrashItemMetadata::new(TrashItemSize::Bytes(0), ExistingKind::File),
+            };
+            // Kept for `deep_metadata`, since the item may have changed by the time it's asked for.
+            if let Ok((total_size, inode_count)) = usage(&path) {
+                metadata.total_size = Some(total_size);
+                metadata.inode_count = Some(inode_count);
+            }
+            state.existing.remove(&path);
+            state.next_id += 1;
+            let id = OsString::from(format!("memory:{}", state.next_id));
+            let item = TrashItem { id, name, original_parent, time_deleted };
+            state.items.push((item.clone(), metadata));
+            items.push(item);
+        }
+        Ok(with_info.then_some(items))
+    }
+
+    fn list(&self) -> Result<Vec<TrashItem>, Error> {
+        Ok(self.items())
+    }
+
+    fn is_empty(&self) -> Result<bool, Error> {
+        Ok(self.state.lock().unwrap().items.is_empty())
+    }
+
+    fn metadata(&self, item: &TrashItem) -> Result<TrashItemMetadata, Error> {
+        let mut metadata = self.deep_metadata(item)?;
+        metadata.total_size = None;
+        metadata.inode_count = None;
+        Ok(metadata)
+    }
+
+    fn deep_metadata(&self, item: &TrashItem) -> Result<TrashItemMetadata, Error> {
+        let state = self.state.lock().unwrap();
+        state
+            .items
+            .iter()
+            .find(|(known, _)| known == item)
+            .map(|(_, metadata)| metadata.clone())
+            .ok_or_else(|| not_in_trash(item))
+    }
+
+    fn restore_status(&self, item: &TrashItem) -> Option<RestoreStatus> {
+        Some(match self.existing(item.original_path()) {
+            Some(kind) => RestoreStatus::Collision(kind),
+            None => RestoreStatus::Ready,
+        })
+    }
+
+    fn purge_all(&self, items: Vec<TrashItem>) -> Result<(), Error> {
+        let mut state = self.state.lock().unwrap();
+        state.check_present(&items)?;
+        for item in &items {
+            state.remove(item);
+        }
+        Ok(())
+    }
+
+    fn restore_all(&self, items: Vec<TrashItem>) -> Result<(), Error> {
+        let mut state = self.state.lock().unwrap();
+        state.check_present(&items)?;
+        for (i, item) in items.iter().enumerate() {
+            let path = item.original_path();
+            if items[..i].iter().any(|earlier| earlier.original_path() == path) {
+                return Err(Error::RestoreTwins { path, items });
+            }
+        }
+        // Restore the items in order until one collides, like the trash of the operating system
+        let mut iter = items.into_iter();
+        while let Some(item) = iter.next() {
+            let path = item.original_path();
+            if state.existing.contains_key(&path) {
+                let remaining_items = std::iter::once(item).chain(iter).collect();
+                return Err(Error::RestoreCollision { path, remaining_items });
+            }
+            let metadata = state.remove(&item);
+            state.existing.insert(path, metadata.kind);
+        }
+        Ok(())
+    }
+}
+
+fn not_in_trash(item: &TrashItem) -> Error {
+    Error::Unknown { description: format!("The item {:?} is not in the trash", item.id) }
+}
+
+/// Returns the total size in bytes and the number of files and directories of the item at `path`.
+fn usage(path: &Path) -> std::io::Result<(u64, u64)> {
+    let metadata = fs::symlink_metadata(path)?;
+    if !metadata.is_dir() {
+        return Ok((metadata.len(), 1));
+    }
+    let (mut size, mut count) = (0, 1);
+    for entry in fs::read_dir(path)? {
+        let (entry_size, entry_count) = usage(&entry?.path())?;
+        size += entry_size;
+        count += entry_count;
+    }
+    Ok((size, count))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use super::{MemoryBackend, TrashBackend};
+    use crate::{tests::get_unique_name, Error, TrashContext};
+
+    #[test]
+    fn delete_is_recorded_without_touching_files() {
+        let backend = MemoryBackend::new();
+        let ctx = TrashContext::with_backend(backend.clone());
+        let name = get_unique_name();
+
+        let items = ctx.delete_all_with_info([&name, "Cargo.toml"]).unwrap().unwrap();
+        assert_eq!(items.len(), 2);
+        assert_eq!(backend.items(), items);
+        assert_eq!(items[0].name, *name);
+        assert_eq!(items[1].original_path(), Path::new(env!("CARGO_MANIFEST_DIR")).join("Cargo.toml"));
+        assert!(Path::new("Cargo.toml").is_file());
+    }
+
+    #[test]
+    fn delete_records_nothing_if_a_path_is_invalid() {
+        let backend = MemoryBackend::new();
+        let paths = vec![Path::new(env!("CARGO_MANIFEST_DIR")).join("Cargo.toml"), "/".into()];
+        assert!(matches!(backend.delete_all(paths, true), Err(Error::TargetedRoot)));
+        assert!(backend.items().is_empty());
+    }
+
+    #[cfg(any(
+        target_os = "windows",
+        all(unix, not(target_os = "macos"), not(target_os = "ios"), not(target_os = "android"))
+    ))]
+    #[test]
+    fn purge_and_restore() {
+        let backend = MemoryBackend::new();
+        let ctx = TrashContext::with_backend(backend.clone());
+        ctx.delete_all([get_unique_name(), get_unique_name(), "Cargo.toml".into()]).unwrap();
+
+        let items = ctx.list().unwrap();
+        assert_eq!(
+            ctx.metadata(&items[2]).unwrap().size.size(),
+            Some(Path::new("Cargo.toml").metadata().unwrap().len())
+        );
+        ctx.purge_all(&items[..1]).unwrap();
+        ctx.restore_all(items[1..].to_vec()).unwrap();
+        assert!(ctx.is_empty().unwrap());
+        assert!(ctx.purge_all(&items[..1]).is_err());
+    }
+
+    #[cfg(any(
+        target_os = "windows",
+        all(unix, not(target_os = "macos"), not(target_os = "ios"), not(target_os = "android"))
+    ))]
+    #[test]
+    fn restore_collisions() {
+        use crate::{ConflictStrategy, Error, ExistingKind, RestoreOptions, RestoreStatus};
+
+        let backend = MemoryBackend::new();
+        let ctx = TrashContext::with_backend(backend.clone());
+        let name = get_unique_name();
+        let first = ctx.delete_with_info(&name).unwrap().unwrap();
+        let second = ctx.delete_with_info(&name).unwrap().unwrap();
+        backend.add_existing(first.original_path(), ExistingKind::Directory);
+
+        let plan = ctx.plan_restore([first.clone()]);
+        assert_eq!(plan.items[0].status, RestoreStatus::Collision(ExistingKind::Directory));
+        assert!(matches!(ctx.restore_all([first.clone(), second.clone()]), Err(Error::RestoreTwins { .. })));
+        match ctx.restore_all([first.clone()]) {
+            Err(Error::RestoreCollision { path, remaining_items }) => {
+                assert_eq!(path, first.original_path());
+                assert_eq!(remaining_items, std::slice::from_ref(&first));
+            }
+            other => panic!("unexpected result {other:?}"),
+        }
+
+        // Overwriting moves the existing item to the trash
+        let overwrite = RestoreOptions::new().conflict(ConflictStrategy::Overwrite);
+        ctx.restore_all_with_options([first.clone()], &overwrite).unwrap();
+        assert_eq!(backend.existing(first.original_path()), Some(ExistingKind::File));
+        assert_eq!(ctx.list().unwrap().len(), 2);
+
+        let rename = RestoreOptions::new().conflict(ConflictStrategy::Rename).transactional(true);
+        let outcomes = ctx.restore_all_with_outcomes([second], &rename).unwrap();
+        let renamed = first.original_parent.join(format!("{name} (restored)"));
+        assert_eq!(outcomes[0].path.as_ref(), Some(&renamed));
+        assert_eq!(backend.existing(renamed), Some(ExistingKind::File));
+    }
+
+    #[cfg(any(
+        target_os = "windows",
+        all(unix, not(target_os = "macos"), not(target_os = "ios"), not(target_os = "android"))
+    ))]
+    #[test]
+    fn deep_metadata_and_unsupported_operations() {
+        let ctx = TrashContext::with_backend(MemoryBackend::new());
+        let item = ctx.delete_with_info("src").unwrap().unwrap();
+        assert_eq!(ctx.metadata(&item).unwrap().total_size, None);
+        let metadata = ctx.deep_metadata(&item).unwrap();
+        assert!(metadata.total_size.unwrap() > Path::new("src/lib.rs").metadata().unwrap().len());
+        assert!(metadata.inode_count.unwrap() > 1);
+
+        #[cfg(all(unix, not(target_os = "macos"), not(target_os = "ios"), not(target_os = "android")))]
+        {
+            assert!(ctx.check_trash().is_err());
+            assert!(ctx.repair(&crate::freedesktop::RepairOptions::new()).is_err());
+        }
+    }
+}
@@ -8,28 +8,215 @@
 
 use std::{
     borrow::{Borrow, Cow},
-    collections::HashSet,
+    collections::{HashMap, HashSet},
     ffi::{OsStr, OsString},
-    fs::{self, File, OpenOptions},
-    io::{BufRead, BufReader, Write},
+    fs::{self, DirBuilder, File, OpenOptions},
+    io::{Read, Write},
     os::unix::{
         ffi::{OsStrExt, OsStringExt},
-        fs::PermissionsExt,
+        fs::{DirBuilderExt, MetadataExt, OpenOptionsExt, PermissionsExt},
     },
     path::{Component, Path, PathBuf},
+    sync::atomic::{AtomicUsize, Ordering},
 };
 
 use log::{debug, warn};
 
-use crate::{Error, TrashContext, TrashItem, TrashItemMetadata, TrashItemSize};
+use crate::{
+    os_limited::restored_name, ConflictStrategy, Error, ListError, ListErrorKind, ListIssue, ListIssueReason,
+    TrashContext, TrashItem, TrashItemMetadata, TrashItemSize,
+};
 
 type FsError = (PathBuf, std::io::Error);
 
-#[derive(Clone, Default, Debug)]
-pub struct PlatformTrashContext;
+/// Which trash folders are used for items that don't reside on the same mount point as the home trash.
+///
+/// Each mount point ("topdir") may contain two kinds of trash folders according to the specification:
+/// `$topdir/.Trash/$uid`, set up by an administrator, and `$topdir/.Trash-$uid`, created on demand.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum TopdirPolicy {
+    /// Use `$topdir/.Trash/$uid` if `$topdir/.Trash` is valid, otherwise `$topdir/.Trash-$uid`.
+    ///
+    /// This is the default and matches the specification.
+    AdminThenUser,
+
+    /// Only use `$topdir/.Trash-$uid`, ignoring any `$topdir/.Trash` folder.
+    UserOnly,
+
+    /// Never use the trash folders of other mount points; every item is moved to the home trash.
+    ///
+    /// Note that items on another file system are copied to the home trash and removed afterwards,
+    /// which is a lot slower than moving them.
+    HomeOnly,
+}
+impl TopdirPolicy {
+    /// Returns `TopdirPolicy::AdminThenUser`
+    pub const fn new() -> Self {
+        TopdirPolicy::AdminThenUser
+    }
+}
+impl Default for TopdirPolicy {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct PlatformTrashContext {
+    home_trash: Option<PathBuf>,
+    topdir_policy: TopdirPolicy,
+    home_trash_fallback: bool,
+    mount_points: Option<Vec<MountPoint>>,
+    index_dir: Option<PathBuf>,
+}
+impl Default for PlatformTrashContext {
+    fn default() -> Self {
+        Self::new()
+    }
+}
 impl PlatformTrashContext {
     pub const fn new() -> Self {
-        PlatformTrashContext
+        Self {
+            home_trash: None,
+            topdir_policy: TopdirPolicy::new(),
+            home_trash_fallback: true,
+            mount_points: None,
+            index_dir: None,
+        }
+    }
+
+    /// The configured home trash, or the one derived from `XDG_DATA_HOME` or `HOME` otherwise.
+    fn home_trash(&self) -> Result<PathBuf, Error> {
+        match &self.home_trash {
+            Some(home_trash) => Ok(home_trash.clone()),
+            None => home_trash(),
+        }
+    }
+
+    fn home_topdir(&self, mnt_points: &[MountPoint]) -> Result<PathBuf, Error> {
+        match &self.home_trash {
+            Some(home_trash) => Ok(get_first_topdir_containing_path(home_trash, mnt_points).to_owned()),
+            None => home_topdir(mnt_points),
+        }
+    }
+}
+/// Freedesktop specific settings of a [`TrashContext`].
+pub trait TrashContextExtFreedesktop {
+    /// Sets the folder used as the home trash, instead of `$XDG_DATA_HOME/Trash`.
+    ///
+    /// The path should be absolute. `None` restores the default, which reads `XDG_DATA_HOME` and `HOME`
+    /// from the environment of the process every time an operation is performed.
+    fn set_home_trash(&mut self, path: Option<PathBuf>);
+    /// The folder set with [`set_home_trash`](TrashContextExtFreedesktop::set_home_trash), if any.
+    fn home_trash(&self) -> Option<&Path>;
+    /// Sets which trash folders are used for items on other mount points than the home trash.
+    ///
+    /// The default is [`TopdirPolicy::AdminThenUser`], as the specification requires.
+    fn set_topdir_policy(&mut self, policy: TopdirPolicy);
+    /// The policy set with [`set_topdir_policy`](TrashContextExtFreedesktop::set_topdir_policy).
+    fn topdir_policy(&self) -> TopdirPolicy;
+    /// Sets whether items are moved to the home trash when no trash folder can be used on their mount point.
+    ///
+    /// This happens when `$topdir/.Trash-$uid` can't be created, for example because the mount point
+    /// is read-only or its root is not writable by the current user. Items on another file system are
+    /// copied to the home trash then. The default is `true`; with `false` such items fail to be deleted.
+    fn set_home_trash_fallback(&mut self, fallback: bool);
+    /// Whether items fall back to the home trash, see
+    /// [`set_home_trash_fallback`](TrashContextExtFreedesktop::set_home_trash_fallback).
+    fn home_trash_fallback(&self) -> bool;
+    /// Sets the mount table used to find the topdir of items and the trash folders on other mount points.
+    ///
+    /// `None` restores the default, which reads the mount table of the system every time an operation
+    /// is performed.
+    fn set_mount_points(&mut self, mount_points: Option<Vec<MountPoint>>);
+    /// The mount table set with [`set_mount_points`](TrashContextExtFreedesktop::set_mount_points), if any.
+    fn mount_points(&self) -> Option<&[MountPoint]>;
+    /// Sets a folder in which [`list`](TrashContext::list) keeps an index of every trash folder.
+    ///
+    /// With an index, only the info files that were added or changed since the last listing are
+    /// read. The index is only a cache and is rebuilt whenever it's missing or unreadable. Note that
+    /// an info file that's overwritten in place without changing the `info` folder is only noticed
+    /// once the folder changes. `None`, the default, disables the index.
+    fn set_index_dir(&mut self, index_dir: Option<PathBuf>);
+    /// The folder set with [`set_index_dir`](TrashContextExtFreedesktop::set_index_dir), if any.
+    fn index_dir(&self) -> Option<&Path>;
+}
+impl TrashContextExtFreedesktop for TrashContext {
+    fn set_home_trash(&mut self, path: Option<PathBuf>) {
+        self.platform_specific.home_trash = path;
+    }
+    fn home_trash(&self) -> Option<&Path> {
+        self.platform_specific.home_trash.as_deref()
+    }
+    fn set_topdir_policy(&mut self, policy: TopdirPolicy) {
+        self.platform_specific.topdir_policy = policy;
+    }
+    fn topdir_policy(&self) -> TopdirPolicy {
+        self.platform_specific.topdir_policy
+    }
+    fn set_home_trash_fallback(&mut self, fallback: bool) {
+        self.platform_specific.home_trash_fallback = fallback;
+    }
+    fn home_trash_fallback(&self) -> bool {
+        self.platform_specific.home_trash_fallback
+    }
+    fn set_mount_points(&mut self, mount_points: Option<Vec<MountPoint>>) {
+        self.platform_specific.mount_points = mount_points;
+    }
+    fn mount_points(&self) -> Option<&[MountPoint]> {
+        self.platform_specific.mount_points.as_deref()
+    }
+    fn set_index_dir(&mut self, index_dir: Option<PathBuf>) {
+        self.platform_specific.index_dir = index_dir;
+    }
+    fn index_dir(&self) -> Option<&Path> {
+        self.platform_specific.index_dir.as_deref()
+    }
+}
+/// Freedesktop specific information about a [`TrashItem`], derived from the path of its info file.
+///
+/// # Example
+///
+/// ```
+/// use std::fs::File;
+/// use trash::{delete_with_info, freedesktop::TrashItemExtFreedesktop, os_limited::purge_all, TrashContext};
+///
+/// let filename = "trash-item_ext_freedesktop-example";
+/// File::create_new(filename).unwrap();
+/// let item = delete_with_info(filename).unwrap().unwrap();
+/// assert!(item.info_path().is_file());
+/// assert!(item.path_in_trash().unwrap().starts_with(item.trash_folder().unwrap()));
+/// println!("The trash folder is on {:?}", item.topdir(&TrashContext::default()).unwrap());
+/// purge_all([item]).unwrap();
+/// ```
+pub trait TrashItemExtFreedesktop {
+    /// The path of the item inside of the `files` folder of its trash folder.
+    ///
+    /// `None` if the id of the item is not the path of an info file.
+    fn path_in_trash(&self) -> Option<PathBuf>;
+    /// The path of the `.trashinfo` file of the item, which is its id.
+    fn info_path(&self) -> &Path;
+    /// The trash folder the item is in, like `~/.local/share/Trash` or `$topdir/.Trash-$uid`.
+    ///
+    /// `None` if the id of the item is not the path of an info file.
+    fn trash_folder(&self) -> Option<&Path>;
+    /// The topdir of the mount point the trash folder of the item is on, using the mount table of `ctx`.
+    fn topdir(&self, ctx: &TrashContext) -> Result<PathBuf, Error>;
+}
+impl TrashItemExtFreedesktop for TrashItem {
+    fn path_in_trash(&self) -> Option<PathBuf> {
+        let (trash_folder, name) = trash_folder_and_name(self.info_path())?;
+        Some(trash_folder.join("files").join(name))
+    }
+    fn info_path(&self) -> &Path {
+        Path::new(&self.id)
+    }
+    fn trash_folder(&self) -> Option<&Path> {
+        trash_folder_and_name(self.info_path()).map(|(trash_folder, _)| trash_folder)
+    }
+    fn topdir(&self, ctx: &TrashContext) -> Result<PathBuf, Error> {
+        let sorted_mount_points = get_sorted_mount_points(&ctx.platform_specific)?;
+        Ok(get_first_topdir_containing_path(self.info_path(), &sorted_mount_points).to_owned())
     }
 }
 impl TrashContext {
@@ -38,9 +225,10 @@ impl TrashContext {
         full_paths: Vec<PathBuf>,
         _with_info: bool,
     ) -> Result<Option<Vec<TrashItem>>, Error> {
-        let home_trash = home_trash()?;
-        let sorted_mount_points = get_sorted_mount_points()?;
-        let home_topdir = home_topdir(&sorted_mount_points)?;
+        let ctx = &self.platform_specific;
+        let home_trash = ctx.home_trash()?;
+        let sorted_mount_points = get_sorted_mount_points(ctx)?;
+        let home_topdir = ctx.home_topdir(&sorted_mount_points)?;
         debug!("The home topdir is {:?}", home_topdir);
         let uid = unsafe { libc::getuid() };
         let mut items = Vec::with_capacity(full_paths.len());
@@ -56,47 +244,201 @@ impl TrashContext {
             } else if topdir.to_str() == Some("/var/home") && home_topdir.to_str() == Some("/") {
                 debug!("The topdir is '/var/home' but the home_topdir is '/', moving to the home trash anyway.");
                 items.push(move_to_trash(path, &home_trash, topdir).map_err(|(p, e)| fs_error(p, e))?);
+            } else if ctx.topdir_policy == TopdirPolicy::HomeOnly {
+                debug!(
+                    "The topdir policy doesn't allow trash folders on other mount points, moving to the home trash."
+                );
+                items.push(move_to_trash(path, &home_trash, topdir).map_err(|(p, e)| fs_error(p, e))?);
             } else {
-                execute_on_mounted_trash_folders(uid, topdir, true, true, |trash_path| {
-                    items.push(move_to_trash(&path, trash_path, topdir)?);
-                    Ok(())
-                })
-                .map_err(|(p, e)| fs_error(p, e))?;
+                match topdir_trash_folder(uid, topdir, ctx.topdir_policy) {
+                    Ok(trash_path) => {
+                        items.push(move_to_trash(&path, trash_path, topdir).map_err(|(p, e)| fs_error(p, e))?)
+                    }
+                    Err((p, e)) if ctx.home_trash_fallback => {
+                        warn!("The trash folder {:?} can't be used, moving to the home trash instead. The error was: {:?}", p, e);
+                        items.push(move_to_trash(path, &home_trash, topdir).map_err(|(p, e)| fs_error(p, e))?);
+                    }
+                    Err((p, e)) => return Err(fs_error(p, e)),
+                }
             }
         }
         Ok(Some(items))
     }
 }
 
-pub fn list() -> Result<Vec<TrashItem>, Error> {
-    let EvaluatedTrashFolders { trash_folders, home_error, sorted_mount_points } = eval_trash_folders()?;
-
-    if trash_folders.is_empty() {
-        warn!("No trash folder was found. The error when looking for the 'home trash' was: {:?}", home_error);
-        return Ok(vec![]);
+pub(crate) fn list(ctx: &PlatformTrashContext) -> Result<Vec<TrashItem>, Error> {
+    match &ctx.index_dir {
+        Some(index_dir) => list_indexed(ctx, index_dir),
+        None => list_unindexed(ctx),
     }
-    // List all items from the set of trash folders
-    let mut result = Vec::new();
-    for folder in &trash_folders {
-        // Read the info files for every file
-        let top_dir = get_first_topdir_containing_path(folder, &sorted_mount_points);
-        let info_folder = folder.join("info");
-        if !info_folder.is_dir() {
-            warn!("The path {:?} did not point to a directory, skipping this trash folder.", info_folder);
-            continue;
+}
+
+#[cfg(not(feature = "rayon"))]
+fn list_unindexed(ctx: &PlatformTrashContext) -> Result<Vec<TrashItem>, Error> {
+    Ok(list_entries(ctx, 0)?.filter_map(listed_item).collect())
+}
+
+#[cfg(feature = "rayon")]
+fn list_unindexed(ctx: &PlatformTrashContext) -> Result<Vec<TrashItem>, Error> {
+    use rayon::prelude::*;
+
+    // Reading the folders is cheap compared to opening every info file, so only the latter is parallel.
+    let mut entries = list_entries(ctx, 0)?;
+    let info_files: Vec<_> = std::iter::from_fn(|| entries.next_info_file()).collect();
+    let items = info_files
+        .into_par_iter()
+        .filter_map(|info_file| match info_file {
+            Ok((info_path, top_dir)) => parse_info_entry(info_path, &top_dir),
+            Err(e) => Some(Err(e)),
+        })
+        .filter_map(listed_item)
+        .collect();
+    Ok(items)
+}
+
+fn listed_item(result: Result<TrashItem, ListError>) -> Option<TrashItem> {
+    match result {
+        Ok(item) => Some(item),
+        Err(e) => {
+            warn!("Skipping the trash entry {:?}: {:?}", e.path, e.kind);
+            None
         }
-        let read_dir = match std::fs::read_dir(&info_folder) {
-            Ok(d) => d,
+    }
+}
+
+pub(crate) fn list_with_diagnostics(ctx: &PlatformTrashContext) -> Result<(Vec<TrashItem>, Vec<ListIssue>), Error> {
+    let mut entries = list_entries(ctx, 0)?;
+    let mut items = Vec::new();
+    let mut issues = Vec::new();
+    while let Some(info_file) = entries.next_info_file() {
+        let trash_folder = entries.folder.clone();
+        let issue = |path, reason| ListIssue { path, trash_folder: trash_folder.clone(), reason };
+        let (info_path, top_dir) = match info_file {
+            Ok(info_file) => info_file,
             Err(e) => {
-                // After all the earlier checks, it's still possible that the directory does not exist at this point (or is not readable)
-                // because another process may have deleted it or modified its access rights in the meantime.
-                // So let's just pring a warning and continue to the rest of the folders
-                warn!("The trash info folder {:?} could not be read. Error was {:?}", info_folder, e);
+                issues.push(issue(e.path, ListIssueReason::Skipped(e.kind)));
                 continue;
             }
         };
-        #[cfg_attr(not(feature = "chrono"), allow(unused_labels))]
-        'trash_item: for entry in read_dir {
+        let result = match read_info_entry(&info_path) {
+            Some(Ok(info)) => {
+                if info.deletion_date.is_none() {
+                    issues.push(issue(info_path.clone(), ListIssueReason::MissingDeletionDate));
+                }
+                info_item(info_path, &info, &top_dir)
+            }
+            Some(Err(e)) => Err(e),
+            None => continue,
+        };
+        match result {
+            Ok(item) => items.push(item),
+            Err(e) => issues.push(issue(e.path, ListIssueReason::Skipped(e.kind))),
+        }
+    }
+    Ok((items, issues))
+}
+
+fn list_indexed(ctx: &PlatformTrashContext, index_dir: &Path) -> Result<Vec<TrashItem>, Error> {
+    let EvaluatedTrashFolders { trash_folders, home_error, sorted_mount_points } = eval_trash_folders(ctx)?;
+
+    if trash_folders.is_empty() {
+        warn!("No trash folder was found. The error when looking for the 'home trash' was: {:?}", home_error);
+    }
+    let mut folders: Vec<PathBuf> = trash_folders.into_iter().collect();
+    folders.sort();
+    let mut items = Vec::new();
+    for folder in folders {
+        let top_dir = get_first_topdir_containing_path(&folder, &sorted_mount_points);
+        items.extend(list_folder_indexed(index_dir, &folder, top_dir));
+    }
+    Ok(items)
+}
+
+/// Lists the trash lazily, yielding each result along with the number of entries that were consumed
+/// once it's yielded, which is where to continue from.
+pub(crate) fn list_iter(
+    ctx: &PlatformTrashContext,
+    offset: usize,
+) -> Result<impl Iterator<Item = (usize, Result<TrashItem, ListError>)> + Send, Error> {
+    let mut entries = list_entries(ctx, offset)?;
+    Ok(std::iter::from_fn(move || {
+        let result = entries.next()?;
+        Some((entries.consumed, result))
+    }))
+}
+
+fn list_entries(ctx: &PlatformTrashContext, offset: usize) -> Result<ListEntries, Error> {
+    let EvaluatedTrashFolders { trash_folders, home_error, sorted_mount_points } = eval_trash_folders(ctx)?;
+
+    if trash_folders.is_empty() {
+        warn!("No trash folder was found. The error when looking for the 'home trash' was: {:?}", home_error);
+    }
+    // Sort the folders so that an offset refers to the same entries every time
+    let mut folders: Vec<(PathBuf, PathBuf)> = trash_folders
+        .into_iter()
+        .map(|folder| {
+            let top_dir = get_first_topdir_containing_path(&folder, &sorted_mount_points).to_owned();
+            (folder, top_dir)
+        })
+        .collect();
+    folders.sort();
+    Ok(ListEntries { folders: folders.into_iter(), current: None, folder: None, skip: offset, consumed: 0 })
+}
+
+/// Reads the info files of every trash folder lazily, one folder and one entry at a time.
+struct ListEntries {
+    /// The trash folders that are yet to be read, with the topdir of each.
+    folders: std::vec::IntoIter<(PathBuf, PathBuf)>,
+    /// The topdir and the entries of the `info` folder that's being read.
+    current: Option<(PathBuf, fs::ReadDir)>,
+    /// The trash folder that's being read.
+    folder: Option<PathBuf>,
+    /// The number of entries to pass without reading them.
+    skip: usize,
+    /// The number of entries that were passed so far, whether they were skipped, yielded or ignored.
+    ///
+    /// Unreadable `info` folders count as one entry, since they yield one error.
+    consumed: usize,
+}
+
+impl ListEntries {
+    /// Advances to the next info file without reading it, returning its path and the topdir of its
+    /// trash folder.
+    fn next_info_file(&mut self) -> Option<Result<(PathBuf, PathBuf), ListError>> {
+        loop {
+            let Some((top_dir, read_dir)) = &mut self.current else {
+                let (folder, top_dir) = self.folders.next()?;
+                let info_folder = folder.join("info");
+                self.folder = Some(folder);
+                match fs::read_dir(&info_folder) {
+                    Ok(read_dir) => self.current = Some((top_dir, read_dir)),
+                    // It's possible that the directory does not exist or is not readable, since another
+                    // process may have deleted it or modified its access rights in the meantime.
+                    Err(e) => {
+                        self.consumed += 1;
+                        if self.skip > 0 {
+                            self.skip -= 1;
+                        } else {
+                            return Some(Err(ListError {
+                                path: info_folder,
+                                kind: ListErrorKind::UnreadableFolder(e),
+                            }));
+                        }
+                    }
+                }
+                continue;
+            };
+            let Some(entry) = read_dir.next() else {
+                self.current = None;
+                continue;
+            };
+            // Every entry counts, even if it ends up not being yielded, so that the offset of a
+            // resumed listing refers to the same entries.
+            self.consumed += 1;
+            if self.skip > 0 {
+                self.skip -= 1;
+                continue;
+            }
             let info_entry = match entry {
                 Ok(entry) => entry,
                 Err(e) => {
@@ -105,7 +447,7 @@ pub fn list() -> Result<Vec<TrashItem>, Error> {
                     continue;
                 }
             };
-            // Entrty should really be an info file but better safe than sorry
+            // Entry should really be an info file but better safe than sorry
             let file_type = match info_entry.file_type() {
                 Ok(f_type) => f_type,
                 Err(e) => {
@@ -116,95 +458,265 @@ pub fn list() -> Result<Vec<TrashItem>, Error> {
             };
             let info_path = info_entry.path();
             if !file_type.is_file() {
-                warn!("Found an item that's not a file, among the trash info files. This is unexpected. The path to the item is: '{:?}'", info_path);
-                continue;
+                return Some(Err(ListError { path: info_path, kind: ListErrorKind::NotAFile }));
             }
-            let info_file = match File::open(&info_path) {
-                Ok(file) => file,
-                Err(e) => {
-                    // Another thread or process may have removed that entry by now
-                    debug!("Tried opening the trash info '{:?}' but failed with: {}", info_path, e);
-                    continue;
-                }
-            };
-            let id = info_path.clone().into();
-            let mut name = None;
-            let mut original_parent: Option<PathBuf> = None;
-            #[cfg_attr(not(feature = "chrono"), allow(unused_mut))]
-            let mut time_deleted = None;
-
-            let info_reader = BufReader::new(info_file);
-            // Skip 1 because the first line must be "[Trash Info]"
-            'info_lines: for line_result in info_reader.lines().skip(1) {
-                // Another thread or process may have removed the infofile by now
-                let line = if let Ok(line) = line_result {
-                    line
-                } else {
-                    break 'info_lines;
-                };
-                let mut split = line.split('=');
-
-                // Just unwraping here because the system is assumed to follow the specification.
-                let key = split.next().unwrap().trim();
-                let value = split.next().unwrap().trim();
-
-                if key == "Path" {
-                    let value_path = {
-                        let path = Path::new(value);
-                        if path.is_relative() {
-                            decode_uri_path(top_dir.join(path))
-                        } else {
-                            decode_uri_path(path)
-                        }
-                    };
-                    name = value_path.file_name().map(|name| name.to_owned());
-                    let parent = value_path.parent().expect("Absolute path to trashed item should have a parent");
-                    original_parent = Some(parent.into());
-                } else if key == "DeletionDate" {
-                    #[cfg(feature = "chrono")]
-                    {
-                        use chrono::{NaiveDateTime, TimeZone};
-                        let parsed_time = NaiveDateTime::parse_from_str(value, "%Y-%m-%dT%H:%M:%S");
-                        let naive_local = match parsed_time {
-                            Ok(t) => t,
-                            Err(e) => {
-                                log::error!("Failed to parse the deletion date of the trash item {:?}. The deletion date was '{}'. Parse error was: {:?}", name, value, e);
-                                continue 'trash_item;
-                            }
-                        };
-                        let time = chrono::Local.from_local_datetime(&naive_local).earliest();
-                        match time {
-                            Some(time) => time_deleted = Some(time.timestamp()),
-                            None => {
-                                log::error!(
-                                    "Failed to convert the local time to a UTC time. Local time was {:?}",
-                                    naive_local
-                                );
-                                continue 'trash_item;
-                            }
-                        }
+            return Some(Ok((info_path, top_dir.clone())));
+        }
+    }
+}
+
+impl Iterator for ListEntries {
+    type Item = Result<TrashItem, ListError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            match self.next_info_file()? {
+                Ok((info_path, top_dir)) => {
+                    if let Some(result) = parse_info_entry(info_path, &top_dir) {
+                        return Some(result);
                     }
                 }
+                Err(e) => return Some(Err(e)),
             }
-            if let Some(name) = name {
-                if let Some(original_parent) = original_parent {
-                    if time_deleted.is_none() {
-                        warn!("Could not determine the deletion time of the trash item. (The `DeletionDate` field is probably missing from the info file.) The info file path is: '{:?}'", info_path);
-                    }
-                    result.push(TrashItem { id, name, original_parent, time_deleted: time_deleted.unwrap_or(-1) });
-                } else {
-                    warn!("Could not determine the original parent folder of the trash item. (The `Path` field is probably missing from the info file.) The info file path is: '{:?}'", info_path);
+        }
+    }
+}
+
+/// Reads the info file at `info_path` into a [`TrashItem`].
+///
+/// Returns `None` if the info file was removed in the meantime.
+fn parse_info_entry(info_path: PathBuf, top_dir: &Path) -> Option<Result<TrashItem, ListError>> {
+    Some(read_info_entry(&info_path)?.and_then(|info| info_item(info_path, &info, top_dir)))
+}
+
+/// Reads and parses the info file at `info_path`.
+///
+/// Returns `None` if the info file was removed in the meantime.
+fn read_info_entry(info_path: &Path) -> Option<Result<TrashInfo, ListError>> {
+    let content = match fs::read(info_path) {
+        Ok(content) => content,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+            // Another thread or process may have removed that entry by now
+            debug!("Tried reading the trash info '{:?}' but failed with: {}", info_path, e);
+            return None;
+        }
+        Err(e) => return Some(Err(ListError { path: info_path.to_owned(), kind: ListErrorKind::Io(e) })),
+    };
+    Some(TrashInfo::parse(&content).map_err(|e| {
+        let kind = match e {
+            TrashInfoError::MissingPath => ListErrorKind::MissingPath,
+            TrashInfoError::InvalidDeletionDate(value) => ListErrorKind::InvalidDeletionDate(value),
+            e => ListErrorKind::InvalidInfo(e),
+        };
+        ListError { path: info_path.to_owned(), kind }
+    }))
+}
+
+/// Turns the parsed info file at `info_path` into a [`TrashItem`].
+fn info_item(info_path: PathBuf, info: &TrashInfo, top_dir: &Path) -> Result<TrashItem, ListError> {
+    // A relative path is relative to the topdir of the trash folder
+    let original_path = top_dir.join(&info.path);
+    let (Some(name), Some(original_parent)) = (original_path.file_name(), original_path.parent()) else {
+        return Err(ListError { path: info_path, kind: ListErrorKind::MissingPath });
+    };
+    let time_deleted = deletion_time(&info_path, info.deletion_date.as_deref())?;
+    let (name, original_parent) = (name.to_owned(), original_parent.to_owned());
+    Ok(TrashItem { id: info_path.into(), name, original_parent, time_deleted })
+}
+
+/// Converts the `DeletionDate` of the info file at `info_path`, which is in local time, to seconds
+/// since the UNIX epoch.
+fn deletion_time(info_path: &Path, deletion_date: Option<&str>) -> Result<i64, ListError> {
+    match deletion_date {
+        #[cfg(feature = "chrono")]
+        Some(deletion_date) => {
+            use chrono::{NaiveDateTime, TimeZone};
+            let time = NaiveDateTime::parse_from_str(deletion_date, "%Y-%m-%dT%H:%M:%S")
+                .ok()
+                .and_then(|naive_local| chrono::Local.from_local_datetime(&naive_local).earliest());
+            match time {
+                Some(time) => Ok(time.timestamp()),
+                None => {
+                    let kind = ListErrorKind::InvalidDeletionDate(deletion_date.to_owned());
+                    Err(ListError { path: info_path.to_owned(), kind })
                 }
-            } else {
-                warn!("Could not determine the name of the trash item. (The `Path` field is probably missing from the info file.) The info file path is: '{:?}'", info_path);
             }
         }
+        #[cfg(not(feature = "chrono"))]
+        Some(_) => Ok(-1),
+        None => {
+            warn!("Could not determine the deletion time of the trash item. (The `DeletionDate` field is probably missing from the info file.) The info file path is: '{:?}'", info_path);
+            Ok(-1)
+        }
     }
-    Ok(result)
 }
 
-pub fn is_empty() -> Result<bool, Error> {
-    let trash_folders = trash_folders()?;
+/// The content of a `.trashinfo` file, which records where an item in the trash came from.
+///
+/// The file follows the rules of desktop entry files: it consists of groups that start with a
+/// `[Group Name]` header and contain `Key=Value` pairs, with blank lines and `#` comments in between.
+/// The first group must be `[Trash Info]`. Keys and groups that are not part of the specification
+/// are kept, so that writing a parsed file doesn't lose them, while comments are not.
+///
+/// See "Contents of a trash directory" at <https://specifications.freedesktop.org/trash-spec/trashspec-1.0.html>
+///
+/// # Example
+///
+/// ```
+/// use trash::freedesktop::TrashInfo;
+///
+/// let info = TrashInfo::parse(b"[Trash Info]\nPath=/home/me/a%20b\nDeletionDate=2024-01-31T12:00:00\n").unwrap();
+/// assert_eq!(info.path, std::path::Path::new("/home/me/a b"));
+/// assert_eq!(info.deletion_date.as_deref(), Some("2024-01-31T12:00:00"));
+///
+/// let mut written = Vec::new();
+/// info.write(&mut written).unwrap();
+/// assert_eq!(TrashInfo::parse(&written).unwrap(), info);
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TrashInfo {
+    /// The original path of the item, which is either absolute or relative to the topdir of the
+    /// trash folder. It's percent-encoded in the file.
+    pub path: PathBuf,
+    /// The time the item was deleted, in local time and of the form `YYYY-MM-DDThh:mm:ss`.
+    pub deletion_date: Option<String>,
+    /// The other keys of the `[Trash Info]` group with their values, in the order they appeared.
+    pub other_keys: Vec<(String, String)>,
+    /// The groups after the `[Trash Info]` group with their keys, in the order they appeared.
+    pub other_groups: Vec<(String, Vec<(String, String)>)>,
+}
+
+/// Why the content of a `.trashinfo` file is not valid, see [`TrashInfo::parse`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TrashInfoError {
+    /// The content is not valid UTF-8.
+    InvalidUtf8,
+    /// The first group is not `[Trash Info]`, or there is a key before the first group.
+    MissingGroup,
+    /// The line with the given number, starting at 1, is neither blank, a comment, a group header
+    /// nor a key-value pair.
+    InvalidLine(usize),
+    /// The key or group is present more than once.
+    Duplicate(String),
+    /// The `Path` key is missing.
+    MissingPath,
+    /// The value of the `DeletionDate` key is not of the form `YYYY-MM-DDThh:mm:ss`.
+    InvalidDeletionDate(String),
+}
+
+impl std::fmt::Display for TrashInfoError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            TrashInfoError::InvalidUtf8 => write!(f, "The trash info is not valid UTF-8"),
+            TrashInfoError::MissingGroup => write!(f, "The trash info doesn't start with the [Trash Info] group"),
+            TrashInfoError::InvalidLine(line) => write!(f, "Line {line} of the trash info is not valid"),
+            TrashInfoError::Duplicate(name) => write!(f, "{name:?} is present more than once in the trash info"),
+            TrashInfoError::MissingPath => write!(f, "The trash info has no Path key"),
+            TrashInfoError::InvalidDeletionDate(value) => write!(f, "The deletion date {value:?} is not valid"),
+        }
+    }
+}
+impl std::error::Error for TrashInfoError {}
+
+impl TrashInfo {
+    /// The name of the first group.
+    const GROUP: &'static str = "Trash Info";
+
+    /// Creates the info of an item that was at `path`, without a deletion date.
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        Self { path: path.into(), deletion_date: None, other_keys: vec![], other_groups: vec![] }
+    }
+
+    /// Parses the content of a `.trashinfo` file.
+    pub fn parse(content: &[u8]) -> Result<Self, TrashInfoError> {
+        let content = std::str::from_utf8(content).map_err(|_| TrashInfoError::InvalidUtf8)?;
+        let mut groups: Vec<(String, Vec<(String, String)>)> = Vec::new();
+        for (index, line) in content.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            if let Some(name) = line.strip_prefix('[').and_then(|line| line.strip_suffix(']')) {
+                if name.is_empty() || name.contains(['[', ']']) || name.contains(char::is_control) {
+                    return Err(TrashInfoError::InvalidLine(index + 1));
+                }
+                if groups.is_empty() && name != Self::GROUP {
+                    return Err(TrashInfoError::MissingGroup);
+                }
+                if groups.iter().any(|(group, _)| group == name) {
+                    return Err(TrashInfoError::Duplicate(name.into()));
+                }
+                groups.push((name.into(), vec![]));
+                continue;
+            }
+            let Some((key, value)) = line.split_once('=') else {
+                return Err(TrashInfoError::InvalidLine(index + 1));
+            };
+            let (key, value) = (key.trim_end(), value.trim_start());
+            let valid_key = |c: char| c.is_ascii_alphanumeric() || "-[]@_.".contains(c);
+            if key.is_empty() || !key.chars().all(valid_key) {
+                return Err(TrashInfoError::InvalidLine(index + 1));
+            }
+            let Some((_, keys)) = groups.last_mut() else {
+                return Err(TrashInfoError::MissingGroup);
+            };
+            if keys.iter().any(|(known, _)| known == key) {
+                return Err(TrashInfoError::Duplicate(key.into()));
+            }
+            keys.push((key.into(), value.into()));
+        }
+        if groups.is_empty() {
+            return Err(TrashInfoError::MissingGroup);
+        }
+        let (_, keys) = groups.remove(0);
+        let mut path = None;
+        let mut deletion_date = None;
+        let mut other_keys = Vec::new();
+        for (key, value) in keys {
+            match key.as_str() {
+                "Path" => path = Some(decode_uri_path(value)),
+                "DeletionDate" if is_deletion_date(&value) => deletion_date = Some(value),
+                "DeletionDate" => return Err(TrashInfoError::InvalidDeletionDate(value)),
+                _ => other_keys.push((key, value)),
+            }
+        }
+        let path = path.ok_or(TrashInfoError::MissingPath)?;
+        Ok(Self { path, deletion_date, other_keys, other_groups: groups })
+    }
+
+    /// Writes the content of a `.trashinfo` file.
+    pub fn write(&self, mut writer: impl Write) -> std::io::Result<()> {
+        writeln!(writer, "[{}]", Self::GROUP)?;
+        writeln!(writer, "Path={}", encode_uri_path(&self.path))?;
+        if let Some(deletion_date) = &self.deletion_date {
+            writeln!(writer, "DeletionDate={deletion_date}")?;
+        }
+        for (key, value) in &self.other_keys {
+            writeln!(writer, "{key}={value}")?;
+        }
+        for (group, keys) in &self.other_groups {
+            writeln!(writer, "\n[{group}]")?;
+            for (key, value) in keys {
+                writeln!(writer, "{key}={value}")?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Whether `value` is of the form `YYYY-MM-DDThh:mm:ss`.
+fn is_deletion_date(value: &str) -> bool {
+    value.len() == 19
+        && value.bytes().enumerate().all(|(i, b)| match i {
+            4 | 7 => b == b'-',
+            10 => b == b'T',
+            13 | 16 => b == b':',
+            _ => b.is_ascii_digit(),
+        })
+}
+
+pub(crate) fn is_empty(ctx: &PlatformTrashContext) -> Result<bool, Error> {
+    let trash_folders = trash_folders(ctx)?;
 
     if trash_folders.is_empty() {
         return Ok(true);
@@ -229,8 +741,8 @@ pub fn is_empty() -> Result<bool, Error> {
     Ok(true)
 }
 
-pub fn trash_folders() -> Result<HashSet<PathBuf>, Error> {
-    let EvaluatedTrashFolders { trash_folders, home_error, .. } = eval_trash_folders()?;
+pub(crate) fn trash_folders(ctx: &PlatformTrashContext) -> Result<HashSet<PathBuf>, Error> {
+    let EvaluatedTrashFolders { trash_folders, home_error, .. } = eval_trash_folders(ctx)?;
 
     if trash_folders.is_empty() {
         return match home_error {
@@ -250,12 +762,12 @@ struct EvaluatedTrashFolders {
     sorted_mount_points: Vec<MountPoint>,
 }
 
-fn eval_trash_folders() -> Result<EvaluatedTrashFolders, Error> {
+fn eval_trash_folders(ctx: &PlatformTrashContext) -> Result<EvaluatedTrashFolders, Error> {
     let mut trash_folders = HashSet::new();
     // Get home trash folder and add it to the set of trash folders.
     // It may not exist and that's completely fine as long as there are other trash folders.
     let home_error;
-    match home_trash() {
+    match ctx.home_trash() {
         Ok(home_trash) => {
             if !home_trash.is_dir() {
                 home_error = Some(Error::Unknown {
@@ -275,25 +787,28 @@ fn eval_trash_folders() -> Result<EvaluatedTrashFolders, Error> {
 
     // Get all mount-points and attempt to find a trash folder in each adding them to the SET of
     // trash folders when found one.
+    // Unless the policy restricts the context to the home trash.
     let uid = unsafe { libc::getuid() };
-    let sorted_mount_points = get_sorted_mount_points()?;
-    for mount in &sorted_mount_points {
-        execute_on_mounted_trash_folders(uid, &mount.mnt_dir, false, false, |trash_path| {
-            trash_folders.insert(trash_path);
-            Ok(())
-        })
-        .map_err(|(p, e)| fs_error(p, e))?;
+    let sorted_mount_points = get_sorted_mount_points(ctx)?;
+    if ctx.topdir_policy != TopdirPolicy::HomeOnly {
+        for mount in &sorted_mount_points {
+            execute_on_mounted_trash_folders(uid, &mount.mnt_dir, ctx.topdir_policy, false, false, |trash_path| {
+                trash_folders.insert(trash_path);
+                Ok(())
+            })
+            .map_err(|(p, e)| fs_error(p, e))?;
+        }
     }
 
     Ok(EvaluatedTrashFolders { trash_folders, home_error, sorted_mount_points })
 }
-pub fn metadata(item: &TrashItem) -> Result<TrashItemMetadata, Error> {
+pub(crate) fn metadata(_ctx: &PlatformTrashContext, item: &TrashItem, deep: bool) -> Result<TrashItemMetadata, Error> {
     // When purging an item the "in-trash" filename must be parsed from the trashinfo filename
     // which is the filename in the `id` field.
     let info_file = &item.id;
 
     let file = restorable_file_in_trash_from_info_file(info_file);
-    assert!(virtually_exists(&file).map_err(|e| fs_error(&file, e))?);
+    // Another process may have purged or restored the item in the meantime, which is an error
     let metadata = fs::symlink_metadata(&file).map_err(|e| fs_error(&file, e))?;
     let is_dir = metadata.is_dir();
     let size = if is_dir {
@@ -301,7 +816,17 @@ pub fn metadata(item: &TrashItem) -> Result<TrashItemMetadata, Error> {
     } else {
         TrashItemSize::Bytes(metadata.len())
     };
-    Ok(TrashItemMetadata { size })
+    let mut result = TrashItemMetadata::from_fs(&file, &metadata, size);
+    if is_dir {
+        result.cached_size = cached_directory_size(Path::new(info_file));
+    }
+    if deep {
+        let (total_size, inode_count) =
+            if is_dir { directory_usage(&file).map_err(|e| fs_error(&file, e))? } else { (metadata.len(), 0) };
+        result.total_size = Some(total_size);
+        result.inode_count = Some(inode_count + 1);
+    }
+    Ok(result)
 }
 
 /// The path points to:
@@ -314,7 +839,7 @@ fn virtually_exists(path: &Path) -> std::io::Result<bool> {
     Ok(path.try_exists()? || path.is_symlink())
 }
 
-pub fn purge_all<I>(items: I) -> Result<(), Error>
+pub(crate) fn purge_all<I>(_ctx: &PlatformTrashContext, items: I) -> Result<(), Error>
 where
     I: IntoIterator,
     <I as IntoIterator>::Item: Borrow<TrashItem>,
@@ -330,7 +855,7 @@ where
         let file = restorable_file_in_trash_from_info_file(info_file);
         if file.is_dir() {
             std::fs::remove_dir_all(&file).map_err(|e| fs_error(&file, e))?;
-        // TODO Update directory size cache if there's one.
+            remove_directory_size(Path::new(info_file));
         } else {
             std::fs::remove_file(&file).map_err(|e| fs_error(&file, e))?;
         }
@@ -347,7 +872,7 @@ fn restorable_file_in_trash_from_info_file(info_file: impl AsRef<std::ffi::OsStr
     trash_folder.join("files").join(name_in_trash)
 }
 
-pub fn restore_all<I>(items: I) -> Result<(), Error>
+pub(crate) fn restore_all<I>(_ctx: &PlatformTrashContext, items: I) -> Result<(), Error>
 where
     I: IntoIterator<Item = TrashItem>,
 {
@@ -364,9 +889,9 @@ where
         // that either there's a bug in this code or the target system didn't follow
         // the specification.
         let file = restorable_file_in_trash_from_info_file(info_file);
-        assert!(virtually_exists(&file).map_err(|e| fs_error(&file, e))?);
-        // TODO add option to forcefully replace any target at the restore location
-        // if it already exists.
+        if !virtually_exists(&file).map_err(|e| fs_error(&file, e))? {
+            return Err(fs_error(&file, std::io::ErrorKind::NotFound.into()));
+        }
         let original_path = item.original_path();
         // Make sure the parent exists so that `create_dir` doesn't faile due to that.
         std::fs::create_dir_all(&item.original_parent).map_err(|e| fs_error(&item.original_parent, e))?;
@@ -395,85 +920,383 @@ where
             let remaining: Vec<_> = std::iter::once(item).chain(iter).collect();
             return Err(Error::RestoreCollision { path: original_path, remaining_items: remaining });
         }
-        std::fs::rename(&file, &original_path).map_err(|e| fs_error(&file, e))?;
+        let is_dir = file.is_dir();
+        if let Err(e) = std::fs::rename(&file, &original_path) {
+            if !is_cross_device(&e) {
+                if let Err(remove_err) = remove_placeholder(&original_path) {
+                    warn!("Failed to remove the placeholder {:?}. The error was: {:?}", original_path, remove_err);
+                }
+                return Err(fs_error(&file, e));
+            }
+            debug!("{:?} and {:?} are on different file systems, copying instead.", file, original_path);
+            move_across_file_systems(&file, &original_path).map_err(|(p, e)| fs_error(p, e))?;
+        }
+        if is_dir {
+            remove_directory_size(Path::new(info_file));
+        }
         std::fs::remove_file(info_file).map_err(|e| fs_error(info_file, e))?;
     }
     Ok(())
 }
 
-/// According to the specification (see at the top of the file) there are two kinds of
-/// trash-folders for a mounted drive or partition.
-/// 1, .Trash/uid
-/// 2, .Trash-uid
+/// Restores either all items or none of them.
 ///
-/// This function executes `op` providing it with a
-/// trash-folder path that's associated with the partition mounted at `topdir`.
-///
-fn execute_on_mounted_trash_folders<F: FnMut(PathBuf) -> Result<(), FsError>>(
-    uid: u32,
-    topdir: impl AsRef<Path>,
-    first_only: bool,
-    create_folder: bool,
-    mut op: F,
-) -> Result<(), FsError> {
-    // See if there's a ".Trash" directory at the mounted location
-    let topdir = topdir.as_ref();
-    let trash_path = topdir.join(".Trash");
-    if trash_path.is_dir() {
-        let validity = folder_validity(&trash_path)?;
-        if validity == TrashValidity::Valid {
-            let users_trash_path = trash_path.join(uid.to_string());
-            if users_trash_path.exists() && users_trash_path.is_dir() {
-                op(users_trash_path)?;
-                if first_only {
-                    return Ok(());
+/// Every item is checked before anything is moved. The info files are only removed once every
+/// item is restored, so that the items restored until a failure can be moved back into the trash.
+pub(crate) fn restore_all_transactional(_ctx: &PlatformTrashContext, items: Vec<TrashItem>) -> Result<(), Error> {
+    check_restore_transactional(&items, &[])?;
+
+    let mut restored = Vec::with_capacity(items.len());
+    for item in &items {
+        match restore_keeping_info(item) {
+            Ok(restored_item) => restored.push(restored_item),
+            Err(error) => {
+                for restored_item in restored.into_iter().rev() {
+                    restored_item.roll_back();
                 }
+                return Err(error);
             }
-        } else {
-            warn!("A Trash folder was found at '{:?}', but it's invalid because it's {:?}", trash_path, validity);
         }
     }
-    // See if there's a ".Trash-$UID" directory at the mounted location
-    let trash_path = topdir.join(format!(".Trash-{uid}"));
-    let should_execute;
-    if !trash_path.exists() || !trash_path.is_dir() {
-        if create_folder {
-            std::fs::create_dir(&trash_path).map_err(|e| (trash_path.to_owned(), e))?;
-            should_execute = true;
-        } else {
-            should_execute = false;
+
+    // Every item is restored at this point, so failing to remove an info file doesn't undo anything.
+    // The info file is left behind instead, which `check_trash` reports as an orphan.
+    for (item, restored_item) in items.iter().zip(restored) {
+        if restored_item.is_dir {
+            remove_directory_size(Path::new(&item.id));
+        }
+        if let Err(e) = std::fs::remove_file(&item.id) {
+            warn!("Failed to remove the info file {:?} of a restored item. The error was: {:?}", item.id, e);
         }
-    } else {
-        should_execute = true;
     }
-    if should_execute {
-        op(trash_path)?;
+    Ok(())
+}
+
+/// Checks that every item can be restored, treating the `taken_paths` that are about to be
+/// overwritten as free.
+pub(crate) fn check_restore_transactional(items: &[TrashItem], taken_paths: &[PathBuf]) -> Result<(), Error> {
+    for item in items {
+        let file = restorable_file_in_trash_from_info_file(&item.id);
+        if !virtually_exists(&file).map_err(|e| fs_error(&file, e))? {
+            return Err(fs_error(&file, std::io::ErrorKind::NotFound.into()));
+        }
+        let original_path = item.original_path();
+        if original_path.symlink_metadata().is_ok() && !taken_paths.contains(&original_path) {
+            return Err(Error::RestoreCollision { path: original_path, remaining_items: items.to_vec() });
+        }
+        // The parent must either exist or be creatable in the nearest folder that exists
+        let existing_ancestor = original_path.ancestors().skip(1).find(|ancestor| ancestor.symlink_metadata().is_ok());
+        if let Some(ancestor) = existing_ancestor {
+            if !ancestor.is_dir() {
+                let error = std::io::Error::other("The parent of the item is not a directory");
+                return Err(fs_error(ancestor, error));
+            }
+            if !is_writable_dir(ancestor) {
+                return Err(fs_error(ancestor, std::io::ErrorKind::PermissionDenied.into()));
+            }
+        }
     }
     Ok(())
 }
 
-fn move_to_trash(
-    src: impl AsRef<Path>,
-    trash_folder: impl AsRef<Path>,
-    _topdir: impl AsRef<Path>,
-) -> Result<TrashItem, FsError> {
-    let src = src.as_ref();
-    let trash_folder = trash_folder.as_ref();
-    let files_folder = trash_folder.join("files");
-    let info_folder = trash_folder.join("info");
+/// An item that was restored by [`restore_keeping_info`], while its info file is still in the trash.
+struct RestoredItem {
+    file: PathBuf,
+    original_path: PathBuf,
+    is_dir: bool,
+    /// The folders that were created for the item, starting with the outermost one.
+    created_folders: Vec<PathBuf>,
+}
 
-    // Ensure the `files` and `info` folders exist
-    std::fs::create_dir_all(&files_folder).map_err(|e| (files_folder.to_owned(), e))?;
-    std::fs::create_dir_all(&info_folder).map_err(|e| (info_folder.to_owned(), e))?;
+impl RestoredItem {
+    /// Moves the item back into the trash and removes the folders that were created for it.
+    fn roll_back(self) {
+        if let Err((path, e)) = move_items_no_replace(&self.original_path, &self.file) {
+            warn!("Failed to move {:?} back into the trash. The error at {:?} was: {:?}", self.original_path, path, e);
+        }
+        remove_created_folders(&self.created_folders);
+    }
+}
 
-    // This kind of validity must only apply ot administrator style trash folders
-    // See Trash directories, (1) at https://specifications.freedesktop.org/trash-spec/trashspec-1.0.html
-    //assert_eq!(folder_validity(trash_folder)?, TrashValidity::Valid);
+fn restore_keeping_info(item: &TrashItem) -> Result<RestoredItem, Error> {
+    let file = restorable_file_in_trash_from_info_file(&item.id);
+    let original_path = item.original_path();
+    let is_dir = file.is_dir();
+    let mut created_folders: Vec<_> = item
+        .original_parent
+        .ancestors()
+        .take_while(|ancestor| ancestor.symlink_metadata().is_err())
+        .map(Path::to_owned)
+        .collect();
+    created_folders.reverse();
+    std::fs::create_dir_all(&item.original_parent).map_err(|e| fs_error(&item.original_parent, e))?;
+    if let Err((path, e)) = move_items_no_replace(&file, &original_path) {
+        remove_created_folders(&created_folders);
+        return Err(if e.kind() == std::io::ErrorKind::AlreadyExists {
+            Error::RestoreCollision { path: original_path, remaining_items: vec![item.clone()] }
+        } else {
+            fs_error(path, e)
+        });
+    }
+    Ok(RestoredItem { file, original_path, is_dir, created_folders })
+}
 
-    // When trashing a file one must make sure that every trashed item is uniquely named.
-    // However the `rename` function -that is used in *nix systems to move files- by default
-    // overwrites the destination. Therefore when multiple threads are removing items with identical
-    // names, an implementation might accidently overwrite an item that was just put into the trash
+fn remove_created_folders(created_folders: &[PathBuf]) {
+    for folder in created_folders.iter().rev() {
+        if let Err(e) = std::fs::remove_dir(folder) {
+            warn!("Failed to remove the folder {:?} that was created for restoring. The error was: {:?}", folder, e);
+        }
+    }
+}
+
+impl TrashContext {
+    /// Merges a trashed directory into the existing directory at its original path.
+    ///
+    /// Returns `false` without changing anything if either of them is not a directory. The info file
+    /// is only removed once every entry was moved out of the trash.
+    pub(crate) fn restore_merging(&self, item: &TrashItem, entry_conflict: ConflictStrategy) -> Result<bool, Error> {
+        let file = restorable_file_in_trash_from_info_file(&item.id);
+        let original_path = item.original_path();
+        if !is_real_dir(&file) || !is_real_dir(&original_path) {
+            return Ok(false);
+        }
+        // A conflict must not leave the item merged in part, so they are looked for upfront
+        if entry_conflict == ConflictStrategy::Fail {
+            if let Some(path) = first_merge_conflict(&file, &original_path)? {
+                return Err(Error::RestoreCollision { path, remaining_items: vec![item.clone()] });
+            }
+        }
+        debug!("Merging {:?} into the existing directory {:?}", file, original_path);
+        let result = self.merge_directory(item, &file, &original_path, entry_conflict);
+        let info_file = Path::new(&item.id);
+        if fs::read_dir(&file).map_err(|e| fs_error(&file, e))?.next().is_none() {
+            fs::remove_dir(&file).map_err(|e| fs_error(&file, e))?;
+            remove_directory_size(info_file);
+            fs::remove_file(info_file).map_err(|e| fs_error(info_file, e))?;
+        } else {
+            // Update the size of the entries that remain in the trash
+            add_directory_size(info_file);
+        }
+        result.map(|_| true)
+    }
+
+    /// Moves the entries of `src` into `dst`, merging directories that exist in both recursively.
+    /// Directories in `src` are removed once they are empty.
+    fn merge_directory(
+        &self,
+        item: &TrashItem,
+        src: &Path,
+        dst: &Path,
+        entry_conflict: ConflictStrategy,
+    ) -> Result<(), Error> {
+        for entry in fs::read_dir(src).map_err(|e| fs_error(src, e))? {
+            let entry = entry.map_err(|e| fs_error(src, e))?;
+            let src_entry = entry.path();
+            let mut dst_entry = dst.join(entry.file_name());
+            if dst_entry.symlink_metadata().is_ok() {
+                if is_real_dir(&src_entry) && is_real_dir(&dst_entry) {
+                    self.merge_directory(item, &src_entry, &dst_entry, entry_conflict)?;
+                    if fs::read_dir(&src_entry).map_err(|e| fs_error(&src_entry, e))?.next().is_none() {
+                        fs::remove_dir(&src_entry).map_err(|e| fs_error(&src_entry, e))?;
+                    }
+                    continue;
+                }
+                match entry_conflict {
+                    ConflictStrategy::Fail => {
+                        return Err(Error::RestoreCollision { path: dst_entry, remaining_items: vec![item.clone()] })
+                    }
+                    ConflictStrategy::Skip => continue,
+                    ConflictStrategy::Overwrite => {
+                        self.delete(&dst_entry)?;
+                    }
+                    ConflictStrategy::Rename => {
+                        let name = entry.file_name();
+                        dst_entry = (1..)
+                            .map(|attempt| dst.join(restored_name(&name, attempt)))
+                            .find(|candidate| candidate.symlink_metadata().is_err())
+                            .expect("there are infinitely many names to try");
+                    }
+                }
+            }
+            move_items_no_replace(&src_entry, &dst_entry).map_err(|(p, e)| fs_error(p, e))?;
+        }
+        Ok(())
+    }
+}
+
+/// Returns the first entry of `dst` that merging `src` into it would collide with, that is an
+/// entry that exists in both, but not as a directory in both.
+fn first_merge_conflict(src: &Path, dst: &Path) -> Result<Option<PathBuf>, Error> {
+    for entry in fs::read_dir(src).map_err(|e| fs_error(src, e))? {
+        let entry = entry.map_err(|e| fs_error(src, e))?;
+        let dst_entry = dst.join(entry.file_name());
+        if dst_entry.symlink_metadata().is_err() {
+            continue;
+        }
+        if !is_real_dir(&entry.path()) || !is_real_dir(&dst_entry) {
+            return Ok(Some(dst_entry));
+        }
+        if let Some(conflict) = first_merge_conflict(&entry.path(), &dst_entry)? {
+            return Ok(Some(conflict));
+        }
+    }
+    Ok(None)
+}
+
+/// Whether `path` is a directory, without following symbolic links.
+fn is_real_dir(path: &Path) -> bool {
+    path.symlink_metadata().is_ok_and(|metadata| metadata.is_dir())
+}
+
+/// Whether the current user may create items in the directory at `path`.
+pub(crate) fn is_writable_dir(path: &Path) -> bool {
+    let Ok(path) = std::ffi::CString::new(path.as_os_str().as_bytes()) else {
+        return false;
+    };
+    unsafe { libc::access(path.as_ptr(), libc::W_OK | libc::X_OK) == 0 }
+}
+
+/// According to the specification (see at the top of the file) there are two kinds of
+/// trash-folders for a mounted drive or partition.
+/// 1, .Trash/uid
+/// 2, .Trash-uid
+///
+/// This function executes `op` providing it with a
+/// trash-folder path that's associated with the partition mounted at `topdir`.
+/// The first kind is only considered if the `policy` allows it.
+/// With `create_folder`, the missing folder of the user is created in `.Trash` if that's valid,
+/// and `.Trash-uid` is created otherwise.
+///
+fn execute_on_mounted_trash_folders<F: FnMut(PathBuf) -> Result<(), FsError>>(
+    uid: u32,
+    topdir: impl AsRef<Path>,
+    policy: TopdirPolicy,
+    first_only: bool,
+    create_folder: bool,
+    mut op: F,
+) -> Result<(), FsError> {
+    // See if there's a ".Trash" directory at the mounted location
+    let topdir = topdir.as_ref();
+    let trash_path = topdir.join(".Trash");
+    if policy == TopdirPolicy::AdminThenUser && trash_path.is_dir() {
+        let validity = folder_validity(&trash_path)?;
+        if validity == TrashValidity::Valid {
+            let users_trash_path = trash_path.join(uid.to_string());
+            // The specification requires creating the folder of the user right away,
+            // if it fails the folder of the user at the root of the mount point is used.
+            match usable_user_trash_folder(&users_trash_path, uid, create_folder) {
+                Ok(true) => {
+                    op(users_trash_path)?;
+                    if first_only {
+                        return Ok(());
+                    }
+                }
+                Ok(false) => {}
+                Err((path, e)) => warn!(
+                    "The trash folder {:?} can't be used, using '.Trash-{}' instead. The error was: {:?}",
+                    path, uid, e
+                ),
+            }
+        } else {
+            warn!("A Trash folder was found at '{:?}', but it's invalid because it's {:?}", trash_path, validity);
+        }
+    }
+    // See if there's a ".Trash-$UID" directory at the mounted location
+    let trash_path = topdir.join(format!(".Trash-{uid}"));
+    if usable_user_trash_folder(&trash_path, uid, create_folder)? {
+        op(trash_path)?;
+    }
+    Ok(())
+}
+
+/// Returns whether the trash folder of the user at `path` exists and is valid.
+///
+/// With `create_folder`, a missing folder is created and an invalid folder is an error.
+fn usable_user_trash_folder(path: &Path, uid: u32, create_folder: bool) -> Result<bool, FsError> {
+    if create_folder {
+        ensure_user_trash_folder(path, uid)?;
+        return Ok(true);
+    }
+    match user_folder_validity(path, uid) {
+        Ok(TrashValidity::Valid) => Ok(true),
+        Ok(validity) => {
+            warn!("A Trash folder was found at '{:?}', but it's invalid because it's {:?}", path, validity);
+            Ok(false)
+        }
+        Err((_, e)) if e.kind() == std::io::ErrorKind::NotFound => Ok(false),
+        Err(e) => Err(e),
+    }
+}
+
+/// Creates the trash folder of the user at `path` if it doesn't exist, and makes sure that it's valid.
+fn ensure_user_trash_folder(path: &Path, uid: u32) -> Result<(), FsError> {
+    create_private_dir_all(path)?;
+    match user_folder_validity(path, uid)? {
+        TrashValidity::Valid => Ok(()),
+        validity => Err((
+            path.to_owned(),
+            std::io::Error::new(
+                std::io::ErrorKind::PermissionDenied,
+                format!("The trash folder is invalid because it's {validity:?}"),
+            ),
+        )),
+    }
+}
+
+/// Creates `path` so that it's only accessible by its owner, if it doesn't exist.
+///
+/// Missing parents are created with the default permissions, since they aren't part of the trash.
+fn create_private_dir_all(path: &Path) -> Result<(), FsError> {
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent).map_err(|e| (parent.to_owned(), e))?;
+    }
+    match DirBuilder::new().mode(0o700).create(path) {
+        Err(e) if e.kind() == std::io::ErrorKind::AlreadyExists && path.is_dir() => Ok(()),
+        result => result.map_err(|e| (path.to_owned(), e)),
+    }
+}
+
+/// Returns the trash folder for items on the partition mounted at `topdir`, creating it if necessary.
+///
+/// The `files` and `info` folders are created as well, so that an unusable trash folder is noticed
+/// before anything is written to it.
+fn topdir_trash_folder(uid: u32, topdir: &Path, policy: TopdirPolicy) -> Result<PathBuf, FsError> {
+    let mut trash_folder = None;
+    execute_on_mounted_trash_folders(uid, topdir, policy, true, true, |trash_path| {
+        trash_folder = Some(trash_path);
+        Ok(())
+    })?;
+    // `execute_on_mounted_trash_folders` either fails or creates the folder
+    let trash_folder = trash_folder.expect("a trash folder should have been created");
+    create_private_dir_all(&trash_folder.join("files"))?;
+    create_private_dir_all(&trash_folder.join("info"))?;
+    Ok(trash_folder)
+}
+
+fn move_to_trash(
+    src: impl AsRef<Path>,
+    trash_folder: impl AsRef<Path>,
+    _topdir: impl AsRef<Path>,
+) -> Result<TrashItem, FsError> {
+    let src = src.as_ref();
+    let trash_folder = trash_folder.as_ref();
+    let files_folder = trash_folder.join("files");
+    let info_folder = trash_folder.join("info");
+
+    // Ensure the trash folder and its `files` and `info` folders exist. Trash folders in a topdir are
+    // validated when they are chosen, while the home trash is used as it is, even if it's a symlink.
+    create_private_dir_all(trash_folder)?;
+    create_private_dir_all(&files_folder)?;
+    create_private_dir_all(&info_folder)?;
+
+    // This kind of validity must only apply ot administrator style trash folders
+    // See Trash directories, (1) at https://specifications.freedesktop.org/trash-spec/trashspec-1.0.html
+    //assert_eq!(folder_validity(trash_folder)?, TrashValidity::Valid);
+
+    // When trashing a file one must make sure that every trashed item is uniquely named.
+    // However the `rename` function -that is used in *nix systems to move files- by default
+    // overwrites the destination. Therefore when multiple threads are removing items with identical
+    // names, an implementation might accidently overwrite an item that was just put into the trash
     // if it's not careful enough.
     //
     // The strategy here is to use the `create_new` parameter of `OpenOptions` to
@@ -497,8 +1320,7 @@ fn move_to_trash(
         info_name.push(".trashinfo");
         let info_file_path = info_folder.join(&info_name);
         let info_result = OpenOptions::new().create_new(true).write(true).open(&info_file_path);
-        let mut time_deleted = -1;
-        match info_result {
+        let file = match info_result {
             Err(error) => {
                 if error.kind() == std::io::ErrorKind::AlreadyExists {
                     continue;
@@ -507,31 +1329,31 @@ fn move_to_trash(
                     return Err((info_file_path, error));
                 }
             }
-            Ok(mut file) => {
-                debug!("Successfully created {:?}", info_file_path);
-                // Write the info file before actually moving anything
-                writeln!(file, "[Trash Info]")
-                    .and_then(|_| {
-                        let absolute_uri = encode_uri_path(src);
-                        writeln!(file, "Path={absolute_uri}").and_then(|_| {
-                            #[cfg(feature = "chrono")]
-                            {
-                                let now = chrono::Local::now();
-                                time_deleted = now.timestamp();
-                                writeln!(file, "DeletionDate={}", now.format("%Y-%m-%dT%H:%M:%S"))
-                            }
-                            #[cfg(not(feature = "chrono"))]
-                            {
-                                time_deleted = -1;
-                                Ok(())
-                            }
-                        })
-                    })
-                    .map_err(|e| (info_file_path.to_owned(), e))?;
-            }
-        }
+            Ok(file) => file,
+        };
+        debug!("Successfully created {:?}", info_file_path);
+        // Write the info file before actually moving anything
+        #[cfg_attr(not(feature = "chrono"), allow(unused_mut))]
+        let mut info = TrashInfo::new(src);
+        #[cfg(feature = "chrono")]
+        let time_deleted = {
+            let now = chrono::Local::now();
+            info.deletion_date = Some(now.format("%Y-%m-%dT%H:%M:%S").to_string());
+            now.timestamp()
+        };
+        #[cfg(not(feature = "chrono"))]
+        let time_deleted = -1;
+        info.write(file).map_err(|e| (info_file_path.to_owned(), e))?;
         let path = files_folder.join(&in_trash_name);
         match move_items_no_replace(src, &path) {
+            Err((err_path, error))
+                if error.kind() != std::io::ErrorKind::AlreadyExists && path.symlink_metadata().is_ok() =>
+            {
+                // The item was copied to the trash, but the original could not be removed completely.
+                // Keep the info file, so that the copy remains a valid trash item.
+                warn!("The item {:?} was copied to the trash but couldn't be removed from its original location.", src);
+                return Err((err_path, error));
+            }
             Err((path, error)) => {
                 debug!("Failed moving item to the trash (this is usually OK). {:?}", error);
                 // Try to delete the info file
@@ -546,6 +1368,9 @@ fn move_to_trash(
             }
             Ok(_) => {
                 // We did it!
+                if path.symlink_metadata().is_ok_and(|metadata| metadata.is_dir()) {
+                    add_directory_size(&info_file_path);
+                }
                 return Ok(TrashItem {
                     id: info_file_path.into(),
                     name: filename.into(),
@@ -558,35 +1383,817 @@ fn move_to_trash(
             }
         }
     }
-}
-
-/// An error may mean that a collision was found.
-fn move_items_no_replace(src: impl AsRef<Path>, dst: impl AsRef<Path>) -> Result<(), FsError> {
-    let src = src.as_ref();
-    let dst = dst.as_ref();
-
-    try_creating_placeholders(src, dst)?;
-    std::fs::rename(src, dst).map_err(|e| (src.to_owned(), e))?;
-
-    // Once everything is moved, lets recursively remove the directory
-    if src.is_dir() {
-        std::fs::remove_dir_all(src).map_err(|e| (src.to_owned(), e))?;
+}
+
+/// An error may mean that a collision was found.
+///
+/// When `dst` is on a different file system, `src` is copied to `dst` and removed afterwards.
+/// A copy that fails is rolled back, but if `src` can't be removed once it was copied completely,
+/// the copy is kept and an error is returned nonetheless.
+fn move_items_no_replace(src: impl AsRef<Path>, dst: impl AsRef<Path>) -> Result<(), FsError> {
+    let src = src.as_ref();
+    let dst = dst.as_ref();
+
+    try_creating_placeholders(src, dst)?;
+    if let Err(e) = std::fs::rename(src, dst) {
+        if !is_cross_device(&e) {
+            if let Err(remove_err) = remove_placeholder(dst) {
+                warn!("Failed to remove the placeholder {:?}. The error was: {:?}", dst, remove_err);
+            }
+            return Err((src.to_owned(), e));
+        }
+        debug!("{:?} and {:?} are on different file systems, copying instead.", src, dst);
+        return move_across_file_systems(src, dst);
+    }
+
+    // Once everything is moved, lets recursively remove the directory
+    if src.is_dir() {
+        std::fs::remove_dir_all(src).map_err(|e| (src.to_owned(), e))?;
+    }
+    Ok(())
+}
+
+fn try_creating_placeholders(src: impl AsRef<Path>, dst: impl AsRef<Path>) -> Result<(), FsError> {
+    let src = src.as_ref();
+    let dst = dst.as_ref();
+    let metadata = src.symlink_metadata().map_err(|e| (src.to_owned(), e))?;
+    if metadata.is_dir() {
+        // NOTE create_dir fails if the directory already exists
+        std::fs::create_dir(dst).map_err(|e| (dst.to_owned(), e))?;
+    } else {
+        // Symlink or file
+        OpenOptions::new().create_new(true).write(true).open(dst).map_err(|e| (dst.to_owned(), e))?;
+    }
+    Ok(())
+}
+
+fn is_cross_device(error: &std::io::Error) -> bool {
+    error.raw_os_error() == Some(libc::EXDEV)
+}
+
+fn remove_placeholder(path: &Path) -> std::io::Result<()> {
+    if path.symlink_metadata()?.is_dir() {
+        std::fs::remove_dir(path)
+    } else {
+        std::fs::remove_file(path)
+    }
+}
+
+fn remove_recursively(path: &Path) -> Result<(), FsError> {
+    let metadata = path.symlink_metadata().map_err(|e| (path.to_owned(), e))?;
+    if metadata.is_dir() {
+        std::fs::remove_dir_all(path).map_err(|e| (path.to_owned(), e))
+    } else {
+        std::fs::remove_file(path).map_err(|e| (path.to_owned(), e))
+    }
+}
+
+/// Copies `src` to the placeholder `dst` and removes `src` once the copy is verified.
+///
+/// A copy that fails or doesn't match `src` is removed again, including the placeholder.
+fn move_across_file_systems(src: &Path, dst: &Path) -> Result<(), FsError> {
+    if let Err(copy_err) = copy_recursively(src, dst).and_then(|_| verify_copy(src, dst)) {
+        if let Err(remove_err) = remove_recursively(dst) {
+            warn!("Failed to roll back the partial copy at {:?}. The error was: {:?}", dst, remove_err);
+        }
+        return Err(copy_err);
+    }
+    remove_recursively(src)
+}
+
+/// Copies files, directories and symbolic links from `src` to `dst` including their permissions
+/// and timestamps.
+///
+/// `dst` must be the placeholder created by `try_creating_placeholders`, that is an empty directory
+/// if `src` is a directory, and an empty file otherwise.
+fn copy_recursively(src: &Path, dst: &Path) -> Result<(), FsError> {
+    let metadata = src.symlink_metadata().map_err(|e| (src.to_owned(), e))?;
+    let file_type = metadata.file_type();
+    if file_type.is_dir() {
+        for entry in fs::read_dir(src).map_err(|e| (src.to_owned(), e))? {
+            let entry = entry.map_err(|e| (src.to_owned(), e))?;
+            let entry_dst = dst.join(entry.file_name());
+            try_creating_placeholders(entry.path(), &entry_dst)?;
+            copy_recursively(&entry.path(), &entry_dst)?;
+        }
+        // Set the permissions last, since they may not allow adding the entries
+        fs::set_permissions(dst, metadata.permissions()).map_err(|e| (dst.to_owned(), e))?;
+    } else if file_type.is_symlink() {
+        let target = fs::read_link(src).map_err(|e| (src.to_owned(), e))?;
+        fs::remove_file(dst).map_err(|e| (dst.to_owned(), e))?;
+        std::os::unix::fs::symlink(target, dst).map_err(|e| (dst.to_owned(), e))?;
+    } else if file_type.is_file() {
+        // Also copies the permissions
+        fs::copy(src, dst).map_err(|e| (src.to_owned(), e))?;
+    } else {
+        let error = std::io::Error::new(
+            std::io::ErrorKind::Unsupported,
+            "Only files, directories and symbolic links can be copied",
+        );
+        return Err((src.to_owned(), error));
+    }
+    copy_timestamps(&metadata, dst).map_err(|e| (dst.to_owned(), e))
+}
+
+/// Checks that `dst` has the same structure as `src`, with files of the same content and symbolic
+/// links to the same targets.
+fn verify_copy(src: &Path, dst: &Path) -> Result<(), FsError> {
+    let mismatch = || {
+        let error = std::io::Error::new(std::io::ErrorKind::InvalidData, "The copy doesn't match the original");
+        Err((dst.to_owned(), error))
+    };
+    let src_metadata = src.symlink_metadata().map_err(|e| (src.to_owned(), e))?;
+    let dst_metadata = dst.symlink_metadata().map_err(|e| (dst.to_owned(), e))?;
+    if src_metadata.file_type() != dst_metadata.file_type() {
+        return mismatch();
+    }
+    if src_metadata.is_dir() {
+        let mut src_entries = 0;
+        for entry in fs::read_dir(src).map_err(|e| (src.to_owned(), e))? {
+            let entry = entry.map_err(|e| (src.to_owned(), e))?;
+            verify_copy(&entry.path(), &dst.join(entry.file_name()))?;
+            src_entries += 1;
+        }
+        if fs::read_dir(dst).map_err(|e| (dst.to_owned(), e))?.count() != src_entries {
+            return mismatch();
+        }
+    } else if src_metadata.file_type().is_symlink() {
+        if fs::read_link(src).map_err(|e| (src.to_owned(), e))?
+            != fs::read_link(dst).map_err(|e| (dst.to_owned(), e))?
+        {
+            return mismatch();
+        }
+    } else if src_metadata.len() != dst_metadata.len()
+        || (src_metadata.is_file() && !same_contents(src, dst).map_err(|e| (dst.to_owned(), e))?)
+    {
+        return mismatch();
+    }
+    Ok(())
+}
+
+/// Returns whether the files at `a` and `b` have the same content.
+fn same_contents(a: &Path, b: &Path) -> std::io::Result<bool> {
+    /// Fills `buf` as far as possible, returning how much was read.
+    fn read_chunk(file: &mut File, buf: &mut [u8]) -> std::io::Result<usize> {
+        let mut len = 0;
+        while len < buf.len() {
+            match file.read(&mut buf[len..]) {
+                Ok(0) => break,
+                Ok(read) => len += read,
+                Err(e) if e.kind() == std::io::ErrorKind::Interrupted => {}
+                Err(e) => return Err(e),
+            }
+        }
+        Ok(len)
+    }
+    let (mut a, mut b) = (File::open(a)?, File::open(b)?);
+    let (mut a_buf, mut b_buf) = (vec![0; 64 * 1024], vec![0; 64 * 1024]);
+    loop {
+        let a_len = read_chunk(&mut a, &mut a_buf)?;
+        let b_len = read_chunk(&mut b, &mut b_buf)?;
+        if a_buf[..a_len] != b_buf[..b_len] {
+            return Ok(false);
+        }
+        if a_len == 0 {
+            return Ok(true);
+        }
+    }
+}
+
+fn copy_timestamps(metadata: &fs::Metadata, dst: &Path) -> std::io::Result<()> {
+    use std::os::unix::fs::MetadataExt;
+    let times = [
+        libc::timespec { tv_sec: metadata.atime() as _, tv_nsec: metadata.atime_nsec() as _ },
+        libc::timespec { tv_sec: metadata.mtime() as _, tv_nsec: metadata.mtime_nsec() as _ },
+    ];
+    let dst = std::ffi::CString::new(dst.as_os_str().as_bytes())?;
+    // Don't follow symbolic links, to set the timestamps of the link itself
+    let result = unsafe { libc::utimensat(libc::AT_FDCWD, dst.as_ptr(), times.as_ptr(), libc::AT_SYMLINK_NOFOLLOW) };
+    if result != 0 {
+        return Err(std::io::Error::last_os_error());
+    }
+    Ok(())
+}
+
+/// The name of the directory size cache, which file managers use to show the size of the trash
+/// without walking every directory in it.
+/// See "Directory size cache" at https://specifications.freedesktop.org/trash-spec/trashspec-1.0.html
+const DIRECTORY_SIZES: &str = "directorysizes";
+
+/// An entry of the `directorysizes` file, which is a line of the form `size mtime name`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct DirectorySize {
+    /// The disk space used by the directory in bytes.
+    size: u64,
+    /// The modification time of the info file of the directory, in seconds since the epoch.
+    /// An entry with a different mtime than the info file is outdated.
+    mtime: i64,
+    /// The name of the directory in the `files` folder.
+    name: OsString,
+}
+
+fn read_directory_sizes(trash_folder: &Path) -> std::io::Result<Vec<DirectorySize>> {
+    let content = match fs::read(trash_folder.join(DIRECTORY_SIZES)) {
+        Ok(content) => content,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(vec![]),
+        Err(e) => return Err(e),
+    };
+    let mut entries = Vec::new();
+    for line in content.split(|&b| b == b'\n').filter(|line| !line.is_empty()) {
+        let mut fields = line.splitn(3, |&b| b == b' ');
+        let mut next_field = || fields.next().and_then(|field| std::str::from_utf8(field).ok());
+        let size = next_field().and_then(|size| size.parse().ok());
+        let mtime = next_field().and_then(|mtime| mtime.parse().ok());
+        let name = next_field();
+        match (size, mtime, name) {
+            (Some(size), Some(mtime), Some(name)) => {
+                let name = OsString::from_vec(urlencoding::decode_binary(name.as_bytes()).into_owned());
+                entries.push(DirectorySize { size, mtime, name });
+            }
+            _ => debug!("Skipping the malformed line {:?} of the directory size cache", String::from_utf8_lossy(line)),
+        }
+    }
+    Ok(entries)
+}
+
+/// Replaces the `directorysizes` file of `trash_folder` with `entries`.
+///
+/// The entries are written to a temporary file that is renamed afterwards, so that readers never
+/// see a partially written file, as required by the specification.
+fn write_directory_sizes(trash_folder: &Path, entries: &[DirectorySize]) -> std::io::Result<()> {
+    replace_file(&trash_folder.join(DIRECTORY_SIZES), |file| {
+        for entry in entries {
+            let name = urlencoding::encode_binary(entry.name.as_bytes());
+            writeln!(file, "{} {} {}", entry.size, entry.mtime, name)?;
+        }
+        Ok(())
+    })
+}
+
+/// Replaces the file at `path` with the content written by `write`.
+///
+/// The content is written to a temporary file next to `path` that is renamed afterwards, so that
+/// readers never see a partially written file. The file is only accessible by its owner, since it
+/// may reveal the original paths of the items in the trash.
+fn replace_file(path: &Path, write: impl FnOnce(&mut File) -> std::io::Result<()>) -> std::io::Result<()> {
+    static COUNTER: AtomicUsize = AtomicUsize::new(0);
+    let (Some(parent), Some(name)) = (path.parent(), path.file_name()) else {
+        return Err(std::io::Error::other("The path to replace has no file name"));
+    };
+    let mut temp_name = OsString::from(".");
+    temp_name.push(name);
+    temp_name.push(format!(".{}.{}", std::process::id(), COUNTER.fetch_add(1, Ordering::Relaxed)));
+    let temp_path = parent.join(temp_name);
+    let result = (|| {
+        let mut file = OpenOptions::new().create_new(true).write(true).mode(0o600).open(&temp_path)?;
+        write(&mut file)?;
+        file.sync_all()?;
+        fs::rename(&temp_path, path)
+    })();
+    if result.is_err() {
+        let _ = fs::remove_file(&temp_path);
+    }
+    result
+}
+
+/// Reads the `directorysizes` file of `trash_folder`, applies `update` and writes the file back.
+fn update_directory_sizes(trash_folder: &Path, update: impl FnOnce(&mut Vec<DirectorySize>)) -> std::io::Result<()> {
+    let mut entries = read_directory_sizes(trash_folder)?;
+    update(&mut entries);
+    write_directory_sizes(trash_folder, &entries)
+}
+
+/// Splits the path of an info file into its trash folder and the name of the item in the trash.
+fn trash_folder_and_name(info_file: &Path) -> Option<(&Path, &OsStr)> {
+    Some((info_file.parent()?.parent()?, info_file.file_stem()?))
+}
+
+/// Adds the size of the directory belonging to `info_file` to the directory size cache.
+///
+/// The cache is only an optimization, so failures are logged but not returned.
+fn add_directory_size(info_file: &Path) {
+    let Some((trash_folder, name)) = trash_folder_and_name(info_file) else {
+        return;
+    };
+    let result = (|| {
+        let mtime = info_file.symlink_metadata()?.mtime();
+        let size = directory_size(&trash_folder.join("files").join(name))?;
+        update_directory_sizes(trash_folder, |entries| {
+            entries.retain(|entry| entry.name != name);
+            entries.push(DirectorySize { size, mtime, name: name.to_owned() });
+        })
+    })();
+    if let Err(e) = result {
+        warn!("Failed to add {:?} to the directory size cache. The error was: {:?}", name, e);
+    }
+}
+
+/// Removes the directory belonging to `info_file` from the directory size cache.
+///
+/// The cache is only an optimization, so failures are logged but not returned.
+fn remove_directory_size(info_file: &Path) {
+    let Some((trash_folder, name)) = trash_folder_and_name(info_file) else {
+        return;
+    };
+    let mut found = false;
+    let result = update_directory_sizes(trash_folder, |entries| {
+        let len = entries.len();
+        entries.retain(|entry| entry.name != name);
+        found = entries.len() != len;
+    });
+    if let Err(e) = result {
+        warn!("Failed to remove {:?} from the directory size cache. The error was: {:?}", name, e);
+    } else if !found {
+        debug!("The directory {:?} was not in the directory size cache", name);
+    }
+}
+
+/// Returns the size from the directory size cache, if it's up to date with `info_file`.
+fn cached_directory_size(info_file: &Path) -> Option<u64> {
+    let (trash_folder, name) = trash_folder_and_name(info_file)?;
+    let mtime = info_file.symlink_metadata().ok()?.mtime();
+    let entries = read_directory_sizes(trash_folder)
+        .map_err(|e| debug!("Failed to read the directory size cache of {:?}: {:?}", trash_folder, e))
+        .ok()?;
+    entries.into_iter().find(|entry| entry.name == name && entry.mtime == mtime).map(|entry| entry.size)
+}
+
+/// The disk space used by the directory at `path` and everything in it, without following
+/// symlinks. That's the size the spec asks for in the `directorysizes` file, as `du -B1` reports it.
+fn directory_size(path: &Path) -> std::io::Result<u64> {
+    let mut size = path.symlink_metadata()?.blocks() * 512;
+    for entry in fs::read_dir(path)? {
+        let entry = entry?;
+        size +=
+            if entry.file_type()?.is_dir() { directory_size(&entry.path())? } else { entry.metadata()?.blocks() * 512 };
+    }
+    Ok(size)
+}
+
+/// The total size of all files in `path` and its subdirectories, and the number of entries in
+/// them, without following symlinks.
+fn directory_usage(path: &Path) -> std::io::Result<(u64, u64)> {
+    let (mut size, mut count) = (0, 0);
+    for entry in fs::read_dir(path)? {
+        let entry = entry?;
+        let metadata = entry.metadata()?;
+        if metadata.is_dir() {
+            let (dir_size, dir_count) = directory_usage(&entry.path())?;
+            size += dir_size;
+            count += dir_count;
+        } else {
+            size += metadata.len();
+        }
+        count += 1;
+    }
+    Ok((size, count))
+}
+
+/// A problem in a trash folder, as found by [`TrashContext::check_trash`].
+#[derive(Debug)]
+pub struct TrashProblem {
+    /// The trash folder that has the problem.
+    pub trash_folder: PathBuf,
+    pub kind: TrashProblemKind,
+}
+
+/// What's wrong with a trash folder, see [`TrashProblem`].
+#[derive(Debug)]
+pub enum TrashProblemKind {
+    /// The info file has no item in the `files` folder, which happens when the item was removed
+    /// without the info file, or moving it to the trash was interrupted.
+    OrphanInfo(PathBuf),
+    /// The item in the `files` folder has no info file, so it's not listed and can't be restored.
+    OrphanFile(PathBuf),
+    /// The entry of the `info` folder is not a valid info file.
+    InvalidInfo(PathBuf, ListErrorKind),
+    /// The folder may be accessed by other users.
+    WrongPermissions(PathBuf),
+    /// The folder is owned by another user. [`TrashContext::repair`] can't fix this.
+    WrongOwner(PathBuf),
+    /// The entry of the `directorysizes` file with the given name is outdated, or doesn't belong to a
+    /// directory in the trash.
+    InvalidDirectorySize(OsString),
+}
+
+/// A problem that [`TrashContext::repair`] tried to fix.
+#[derive(Debug)]
+pub struct RepairOutcome {
+    pub problem: TrashProblem,
+    /// Why the problem couldn't be fixed, `None` if it was fixed.
+    pub error: Option<Error>,
+}
+
+/// The problems [`TrashContext::repair`] fixes. Every fix except for
+/// [`remove_invalid_info`](RepairOptions::remove_invalid_info) is enabled by default.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RepairOptions {
+    /// Remove info files that have no item.
+    pub remove_orphan_info: bool,
+    /// Remove entries of the `info` folder that are not valid info files. Their items become
+    /// orphan files then.
+    pub remove_invalid_info: bool,
+    /// Write info files for items that have none, so that they are restored into the given directory.
+    /// `None` leaves orphan files as they are.
+    pub adopt_orphan_files: Option<PathBuf>,
+    /// Remove the permissions of other users from the trash folders.
+    pub fix_permissions: bool,
+    /// Update or remove the invalid entries of the `directorysizes` files.
+    pub fix_directory_sizes: bool,
+}
+
+impl Default for RepairOptions {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl RepairOptions {
+    /// Returns the default options, which fix every problem except for invalid info files and orphan files.
+    pub const fn new() -> Self {
+        Self {
+            remove_orphan_info: true,
+            remove_invalid_info: false,
+            adopt_orphan_files: None,
+            fix_permissions: true,
+            fix_directory_sizes: true,
+        }
+    }
+
+    /// Returns these options with removing orphan info files enabled or disabled.
+    pub const fn remove_orphan_info(mut self, remove: bool) -> Self {
+        self.remove_orphan_info = remove;
+        self
+    }
+
+    /// Returns these options with removing invalid info files enabled or disabled.
+    pub const fn remove_invalid_info(mut self, remove: bool) -> Self {
+        self.remove_invalid_info = remove;
+        self
+    }
+
+    /// Returns these options with orphan files being restored into `dir` later on.
+    pub fn adopt_orphan_files(mut self, dir: impl Into<PathBuf>) -> Self {
+        self.adopt_orphan_files = Some(dir.into());
+        self
+    }
+
+    /// Returns these options with fixing permissions enabled or disabled.
+    pub const fn fix_permissions(mut self, fix: bool) -> Self {
+        self.fix_permissions = fix;
+        self
+    }
+
+    /// Returns these options with fixing the directory size caches enabled or disabled.
+    pub const fn fix_directory_sizes(mut self, fix: bool) -> Self {
+        self.fix_directory_sizes = fix;
+        self
+    }
+}
+
+pub(crate) fn check_trash(ctx: &PlatformTrashContext) -> Result<Vec<TrashProblem>, Error> {
+    let EvaluatedTrashFolders { trash_folders, .. } = eval_trash_folders(ctx)?;
+    let mut trash_folders: Vec<_> = trash_folders.into_iter().collect();
+    trash_folders.sort();
+    let uid = unsafe { libc::getuid() };
+    let mut problems = Vec::new();
+    for trash_folder in trash_folders {
+        let kinds = check_trash_folder(&trash_folder, uid).map_err(|(p, e)| fs_error(p, e))?;
+        problems.extend(kinds.into_iter().map(|kind| TrashProblem { trash_folder: trash_folder.clone(), kind }));
+    }
+    Ok(problems)
+}
+
+fn check_trash_folder(trash_folder: &Path, uid: u32) -> Result<Vec<TrashProblemKind>, FsError> {
+    let mut problems = Vec::new();
+    let info_folder = trash_folder.join("info");
+    let files_folder = trash_folder.join("files");
+    for folder in [trash_folder, &files_folder, &info_folder] {
+        // The trash folder may be a symlink, like the home trash is allowed to be, and what
+        // matters then are the permissions of the folder it links to
+        let metadata = if folder == trash_folder { folder.metadata() } else { folder.symlink_metadata() };
+        match metadata {
+            Ok(metadata) if metadata.uid() != uid => problems.push(TrashProblemKind::WrongOwner(folder.to_owned())),
+            Ok(metadata) if metadata.mode() & 0o077 != 0 => {
+                problems.push(TrashProblemKind::WrongPermissions(folder.to_owned()))
+            }
+            Ok(_) => {}
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+            Err(e) => return Err((folder.to_owned(), e)),
+        }
+    }
+
+    // The names of the items in the `files` folder that have an info file, even an invalid one
+    let mut names_with_info = HashSet::new();
+    for entry in read_dir_if_exists(&info_folder)? {
+        let entry = entry.map_err(|e| (info_folder.clone(), e))?;
+        let info_path = entry.path();
+        let Some(name) = info_path.file_name().and_then(|name| name.as_bytes().strip_suffix(b".trashinfo")) else {
+            debug!("Ignoring {:?}, which is not an info file", info_path);
+            continue;
+        };
+        let name = OsStr::from_bytes(name);
+        names_with_info.insert(name.to_owned());
+        if !entry.file_type().map_err(|e| (info_path.clone(), e))?.is_file() {
+            problems.push(TrashProblemKind::InvalidInfo(info_path, ListErrorKind::NotAFile));
+        } else if let Some(Err(e)) = read_info_entry(&info_path) {
+            problems.push(TrashProblemKind::InvalidInfo(info_path, e.kind));
+        } else if !virtually_exists(&files_folder.join(name)).map_err(|e| (files_folder.join(name), e))? {
+            problems.push(TrashProblemKind::OrphanInfo(info_path));
+        }
+    }
+    for entry in read_dir_if_exists(&files_folder)? {
+        let entry = entry.map_err(|e| (files_folder.clone(), e))?;
+        if !names_with_info.contains(&entry.file_name()) {
+            problems.push(TrashProblemKind::OrphanFile(entry.path()));
+        }
+    }
+
+    let directory_sizes = read_directory_sizes(trash_folder).map_err(|e| (trash_folder.join(DIRECTORY_SIZES), e))?;
+    for entry in directory_sizes {
+        let mut info_name = entry.name.clone();
+        info_name.push(".trashinfo");
+        let is_dir = is_real_dir(&files_folder.join(&entry.name));
+        let info_mtime = info_folder.join(info_name).symlink_metadata().map(|metadata| metadata.mtime());
+        if !is_dir || info_mtime.ok() != Some(entry.mtime) {
+            problems.push(TrashProblemKind::InvalidDirectorySize(entry.name));
+        }
+    }
+    Ok(problems)
+}
+
+/// Reads the directory at `path`, which is treated as empty if it doesn't exist.
+fn read_dir_if_exists(path: &Path) -> Result<impl Iterator<Item = std::io::Result<fs::DirEntry>>, FsError> {
+    match fs::read_dir(path) {
+        Ok(read_dir) => Ok(Some(read_dir).into_iter().flatten()),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(None.into_iter().flatten()),
+        Err(e) => Err((path.to_owned(), e)),
+    }
+}
+
+pub(crate) fn repair(ctx: &PlatformTrashContext, options: &RepairOptions) -> Result<Vec<RepairOutcome>, Error> {
+    let mut outcomes = Vec::new();
+    // Invalid info files are removed first, so that their items are found to be orphan files afterwards
+    if options.remove_invalid_info {
+        for problem in check_trash(ctx)? {
+            if let TrashProblemKind::InvalidInfo(path, _) = &problem.kind {
+                let result = if path.is_dir() { fs::remove_dir_all(path) } else { fs::remove_file(path) };
+                let error = result.map_err(|e| fs_error(path, e)).err();
+                outcomes.push(RepairOutcome { problem, error });
+            }
+        }
+    }
+    for problem in check_trash(ctx)? {
+        let result = match &problem.kind {
+            TrashProblemKind::OrphanInfo(info_file) if options.remove_orphan_info => {
+                fs::remove_file(info_file).map_err(|e| fs_error(info_file, e))
+            }
+            TrashProblemKind::OrphanFile(file) => match &options.adopt_orphan_files {
+                Some(dir) => adopt_orphan_file(&problem.trash_folder, file, dir).map_err(|(p, e)| fs_error(p, e)),
+                None => continue,
+            },
+            TrashProblemKind::WrongPermissions(folder) if options.fix_permissions => {
+                fs::set_permissions(folder, fs::Permissions::from_mode(0o700)).map_err(|e| fs_error(folder, e))
+            }
+            TrashProblemKind::InvalidDirectorySize(name) if options.fix_directory_sizes => {
+                update_directory_sizes(&problem.trash_folder, |entries| entries.retain(|entry| entry.name != *name))
+                    .map_err(|e| fs_error(problem.trash_folder.join(DIRECTORY_SIZES), e))
+                    .map(|()| {
+                        let mut info_name = name.clone();
+                        info_name.push(".trashinfo");
+                        let info_file = problem.trash_folder.join("info").join(info_name);
+                        // The entry of a directory that's still in the trash is outdated, so it's updated
+                        if is_real_dir(&problem.trash_folder.join("files").join(name)) && info_file.is_file() {
+                            add_directory_size(&info_file);
+                        }
+                    })
+            }
+            _ => continue,
+        };
+        outcomes.push(RepairOutcome { problem, error: result.err() });
+    }
+    Ok(outcomes)
+}
+
+/// Writes an info file for the orphan `file`, which makes it restorable into `dir`.
+fn adopt_orphan_file(trash_folder: &Path, file: &Path, dir: &Path) -> Result<(), FsError> {
+    let name = file.file_name().expect("An entry of the files folder has a name");
+    let mut info_name = name.to_owned();
+    info_name.push(".trashinfo");
+    let info_file = trash_folder.join("info").join(info_name);
+    create_private_dir_all(&trash_folder.join("info"))?;
+    #[cfg_attr(not(feature = "chrono"), allow(unused_mut))]
+    let mut info = TrashInfo::new(dir.join(name));
+    #[cfg(feature = "chrono")]
+    {
+        info.deletion_date = Some(chrono::Local::now().format("%Y-%m-%dT%H:%M:%S").to_string());
+    }
+    let info_result = OpenOptions::new().create_new(true).write(true).open(&info_file).and_then(|f| info.write(f));
+    info_result.map_err(|e| (info_file.clone(), e))?;
+    if is_real_dir(file) {
+        add_directory_size(&info_file);
+    }
+    Ok(())
+}
+
+/// The first line of an index file, which changes whenever the format of the index does.
+const INDEX_HEADER: &str = "trash-index 2";
+
+/// An index of the `info` folder of a trash folder, see [`TrashContextExtFreedesktop::set_index_dir`].
+///
+/// An index file consists of the header, the path of the trash folder, the mtime of the `info`
+/// folder, and a line of the form `mtime mtime_nsec len name original_path [deletion_date]` for every
+/// info file, with the name, the original path and the deletion date percent-encoded.
+///
+/// The deletion date is kept as it's written in the info file, since it's in local time and
+/// converting it depends on the current time zone.
+#[derive(Debug, Default)]
+struct TrashIndex {
+    /// The mtime of the `info` folder when it was indexed, `None` if that mtime can't be trusted.
+    info_mtime: Option<(i64, i64)>,
+    entries: Vec<IndexEntry>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct IndexEntry {
+    /// The name of the info file in the `info` folder.
+    name: OsString,
+    /// The mtime and length of the info file when it was read, which must be unchanged for the entry
+    /// to be used.
+    mtime: (i64, i64),
+    len: u64,
+    original_path: PathBuf,
+    deletion_date: Option<String>,
+}
+
+impl IndexEntry {
+    fn item(&self, info_folder: &Path) -> Result<TrashItem, ListError> {
+        let info_path = info_folder.join(&self.name);
+        let (Some(name), Some(original_parent)) = (self.original_path.file_name(), self.original_path.parent()) else {
+            return Err(ListError { path: info_path, kind: ListErrorKind::MissingPath });
+        };
+        let time_deleted = deletion_time(&info_path, self.deletion_date.as_deref())?;
+        let (name, original_parent) = (name.to_owned(), original_parent.to_owned());
+        Ok(TrashItem { id: info_path.into(), name, original_parent, time_deleted })
+    }
+}
+
+/// The index file of `trash_folder` in `index_dir`.
+fn index_path(index_dir: &Path, trash_folder: &Path) -> PathBuf {
+    // 64 bit FNV-1a, which unlike the hasher of the standard library is stable across releases
+    let hash = trash_folder
+        .as_os_str()
+        .as_bytes()
+        .iter()
+        .fold(0xcbf29ce484222325_u64, |hash, &byte| (hash ^ u64::from(byte)).wrapping_mul(0x100000001b3));
+    index_dir.join(format!("{hash:016x}.index"))
+}
+
+/// Reads the index of `trash_folder`, returning `None` if there is none or it's not valid.
+fn read_index(index_path: &Path, trash_folder: &Path) -> Option<TrashIndex> {
+    let content = match fs::read(index_path) {
+        Ok(content) => content,
+        Err(e) => {
+            if e.kind() != std::io::ErrorKind::NotFound {
+                debug!("Failed to read the index {:?}: {:?}", index_path, e);
+            }
+            return None;
+        }
+    };
+    let content = std::str::from_utf8(&content).ok()?;
+    let mut lines = content.lines();
+    if lines.next()? != INDEX_HEADER {
+        return None;
+    }
+    if Path::new(&OsString::from_vec(urlencoding::decode_binary(lines.next()?.as_bytes()).into_owned())) != trash_folder
+    {
+        return None;
+    }
+    let info_mtime = match lines.next()?.split_once(' ') {
+        Some((secs, nsecs)) => Some((secs.parse().ok()?, nsecs.parse().ok()?)),
+        None => None,
+    };
+    let mut entries = Vec::new();
+    for line in lines {
+        let mut fields = line.split(' ');
+        let mut next_field = || fields.next();
+        let mtime = (next_field()?.parse().ok()?, next_field()?.parse().ok()?);
+        let len = next_field()?.parse().ok()?;
+        let name = OsString::from_vec(urlencoding::decode_binary(next_field()?.as_bytes()).into_owned());
+        let original_path =
+            PathBuf::from(OsString::from_vec(urlencoding::decode_binary(next_field()?.as_bytes()).into_owned()));
+        let deletion_date = match next_field() {
+            Some(deletion_date) => Some(urlencoding::decode(deletion_date).ok()?.into_owned()),
+            None => None,
+        };
+        entries.push(IndexEntry { name, mtime, len, original_path, deletion_date });
+    }
+    Some(TrashIndex { info_mtime, entries })
+}
+
+fn write_index(index_path: &Path, trash_folder: &Path, index: &TrashIndex) -> std::io::Result<()> {
+    replace_file(index_path, |file| {
+        writeln!(file, "{INDEX_HEADER}")?;
+        writeln!(file, "{}", urlencoding::encode_binary(trash_folder.as_os_str().as_bytes()))?;
+        match index.info_mtime {
+            Some((secs, nsecs)) => writeln!(file, "{secs} {nsecs}")?,
+            None => writeln!(file, "-")?,
+        }
+        for entry in &index.entries {
+            let name = urlencoding::encode_binary(entry.name.as_bytes());
+            let original_path = urlencoding::encode_binary(entry.original_path.as_os_str().as_bytes());
+            let (secs, nsecs) = entry.mtime;
+            write!(file, "{secs} {nsecs} {} {name} {original_path}", entry.len)?;
+            match &entry.deletion_date {
+                Some(deletion_date) => writeln!(file, " {}", urlencoding::encode(deletion_date))?,
+                None => writeln!(file)?,
+            }
+        }
+        Ok(())
+    })
+}
+
+/// Lists the items of `trash_folder` with the help of its index in `index_dir`, and updates the index.
+///
+/// The index is only a cache, so failures to read or write it are logged but not returned.
+fn list_folder_indexed(index_dir: &Path, trash_folder: &Path, top_dir: &Path) -> Vec<TrashItem> {
+    let info_folder = trash_folder.join("info");
+    // Get the mtime before reading the folder, so that later changes are noticed the next time.
+    let info_mtime = match info_folder.metadata() {
+        Ok(metadata) if metadata.is_dir() => (metadata.mtime(), metadata.mtime_nsec()),
+        Ok(_) => {
+            warn!("The path {:?} did not point to a directory, skipping this trash folder.", info_folder);
+            return vec![];
+        }
+        Err(e) => {
+            warn!("The trash info folder {:?} could not be read. Error was {:?}", info_folder, e);
+            return vec![];
+        }
+    };
+    let index_path = index_path(index_dir, trash_folder);
+    let old_index = read_index(&index_path, trash_folder).unwrap_or_default();
+    if old_index.info_mtime == Some(info_mtime) {
+        return old_index.entries.iter().map(|entry| entry.item(&info_folder)).filter_map(listed_item).collect();
+    }
+    let read_dir = match fs::read_dir(&info_folder) {
+        Ok(read_dir) => read_dir,
+        Err(e) => {
+            warn!("The trash info folder {:?} could not be read. Error was {:?}", info_folder, e);
+            return vec![];
+        }
+    };
+    let mut cached: HashMap<OsString, IndexEntry> =
+        old_index.entries.into_iter().map(|entry| (entry.name.clone(), entry)).collect();
+    let mut index = TrashIndex { info_mtime: trusted_mtime(info_mtime), entries: Vec::new() };
+    let mut items = Vec::new();
+    for info_entry in read_dir.filter_map(Result::ok) {
+        let Ok(metadata) = info_entry.metadata() else { continue };
+        let info_path = info_entry.path();
+        if !metadata.is_file() {
+            warn!("Skipping the trash entry {:?}: {:?}", info_path, ListErrorKind::NotAFile);
+            continue;
+        }
+        let name = info_entry.file_name();
+        let mtime = (metadata.mtime(), metadata.mtime_nsec());
+        let entry = match cached.remove(&name) {
+            Some(entry) if entry.mtime == mtime && entry.len == metadata.len() => entry,
+            _ => match read_info_entry(&info_path) {
+                Some(Ok(info)) => IndexEntry {
+                    name,
+                    mtime,
+                    len: metadata.len(),
+                    original_path: top_dir.join(&info.path),
+                    deletion_date: info.deletion_date,
+                },
+                Some(Err(e)) => {
+                    warn!("Skipping the trash entry {:?}: {:?}", e.path, e.kind);
+                    continue;
+                }
+                None => continue,
+            },
+        };
+        // The entry is kept in the index even if it's not valid, since it's not going to be any more
+        // valid when the info file is read again.
+        match entry.item(&info_folder) {
+            Ok(item) => items.push(item),
+            Err(e) => warn!("Skipping the trash entry {:?}: {:?}", e.path, e.kind),
+        }
+        index.entries.push(entry);
+    }
+    let result = create_private_dir_all(index_dir)
+        .map_err(|(_, e)| e)
+        .and_then(|()| write_index(&index_path, trash_folder, &index));
+    if let Err(e) = result {
+        warn!("Failed to update the index {:?}. The error was: {:?}", index_path, e);
     }
-    Ok(())
+    items
 }
 
-fn try_creating_placeholders(src: impl AsRef<Path>, dst: impl AsRef<Path>) -> Result<(), FsError> {
-    let src = src.as_ref();
-    let dst = dst.as_ref();
-    let metadata = src.symlink_metadata().map_err(|e| (src.to_owned(), e))?;
-    if metadata.is_dir() {
-        // NOTE create_dir fails if the directory already exists
-        std::fs::create_dir(dst).map_err(|e| (dst.to_owned(), e))?;
-    } else {
-        // Symlink or file
-        OpenOptions::new().create_new(true).write(true).open(dst).map_err(|e| (dst.to_owned(), e))?;
-    }
-    Ok(())
+/// Returns `mtime` unless it's so recent that the folder may still change without changing its mtime,
+/// since the resolution of timestamps is limited.
+fn trusted_mtime(mtime: (i64, i64)) -> Option<(i64, i64)> {
+    let now = std::time::SystemTime::now().duration_since(std::time::UNIX_EPOCH).ok()?.as_secs() as i64;
+    (mtime.0 < now - 1).then_some(mtime)
 }
 
 fn decode_uri_path(path: impl AsRef<Path>) -> PathBuf {
@@ -621,12 +2228,14 @@ enum TrashValidity {
     Valid,
     InvalidSymlink,
     InvalidNotSticky,
+    InvalidNotDirectory,
+    InvalidNotOwnedByUser,
 }
 
 fn folder_validity(path: impl AsRef<Path>) -> Result<TrashValidity, FsError> {
     /// Mask for the sticky bit
     /// Taken from: http://man7.org/linux/man-pages/man7/inode.7.html
-    const S_ISVTX: u32 = 0x1000;
+    const S_ISVTX: u32 = 0o1000;
 
     let path = path.as_ref();
     let metadata = path.symlink_metadata().map_err(|e| (path.to_owned(), e))?;
@@ -641,17 +2250,36 @@ fn folder_validity(path: impl AsRef<Path>) -> Result<TrashValidity, FsError> {
     Ok(TrashValidity::Valid)
 }
 
+/// Checks a trash folder that belongs to a single user, i.e. `.Trash-$uid`, `.Trash/$uid` or the home trash.
+///
+/// Such a folder must be a directory owned by `uid` and not a symlink, so that no other user can
+/// read the items or make them end up somewhere else.
+fn user_folder_validity(path: impl AsRef<Path>, uid: u32) -> Result<TrashValidity, FsError> {
+    let path = path.as_ref();
+    let metadata = path.symlink_metadata().map_err(|e| (path.to_owned(), e))?;
+    if metadata.file_type().is_symlink() {
+        return Ok(TrashValidity::InvalidSymlink);
+    }
+    if !metadata.is_dir() {
+        return Ok(TrashValidity::InvalidNotDirectory);
+    }
+    if metadata.uid() != uid {
+        return Ok(TrashValidity::InvalidNotOwnedByUser);
+    }
+    Ok(TrashValidity::Valid)
+}
+
 /// Corresponds to the definition of "home_trash" from
 /// https://specifications.freedesktop.org/trash-spec/trashspec-1.0.html
 fn home_trash() -> Result<PathBuf, Error> {
     if let Some(data_home) = std::env::var_os("XDG_DATA_HOME") {
-        if data_home.len() > 0 {
+        if !data_home.is_empty() {
             let data_home_path = AsRef::<Path>::as_ref(data_home.as_os_str());
             return Ok(data_home_path.join("Trash"));
         }
     }
     if let Some(home) = std::env::var_os("HOME") {
-        if home.len() > 0 {
+        if !home.is_empty() {
             let home_path = AsRef::<Path>::as_ref(home.as_os_str());
             return Ok(home_path.join(".local/share/Trash"));
         }
@@ -661,13 +2289,13 @@ fn home_trash() -> Result<PathBuf, Error> {
 
 fn home_topdir(mnt_points: &[MountPoint]) -> Result<PathBuf, Error> {
     if let Some(data_home) = std::env::var_os("XDG_DATA_HOME") {
-        if data_home.len() > 0 {
+        if !data_home.is_empty() {
             let data_home_path = AsRef::<Path>::as_ref(data_home.as_os_str());
             return Ok(get_first_topdir_containing_path(data_home_path, mnt_points).to_owned());
         }
     }
     if let Some(home) = std::env::var_os("HOME") {
-        if home.len() > 0 {
+        if !home.is_empty() {
             let home_path = AsRef::<Path>::as_ref(home.as_os_str());
             return Ok(get_first_topdir_containing_path(home_path, mnt_points).to_owned());
         }
@@ -680,15 +2308,23 @@ fn get_first_topdir_containing_path<'a>(path: &Path, mnt_points: &'a [MountPoint
     mnt_points.iter().map(|mp| mp.mnt_dir.as_path()).find(|mount_path| path.starts_with(mount_path)).unwrap_or(root)
 }
 
-struct MountPoint {
-    mnt_dir: PathBuf,
-    _mnt_type: String,
-    _mnt_fsname: String,
+/// An entry of the mount table, as returned by `getmntent` or `getmntinfo`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct MountPoint {
+    /// The directory the file system is mounted at, its "topdir".
+    pub mnt_dir: PathBuf,
+    /// The type of the file system, like `ext4`.
+    pub mnt_type: String,
+    /// The mounted device or remote file system.
+    pub mnt_fsname: String,
 }
 
 /// Sorted by longest path first
-fn get_sorted_mount_points() -> Result<Vec<MountPoint>, Error> {
-    let mut mount_points = get_mount_points()?;
+fn get_sorted_mount_points(ctx: &PlatformTrashContext) -> Result<Vec<MountPoint>, Error> {
+    let mut mount_points = match &ctx.mount_points {
+        Some(mount_points) => mount_points.clone(),
+        None => get_mount_points()?,
+    };
     mount_points.sort_unstable_by(|a, b| {
         let a = a.mnt_dir.as_os_str().as_bytes().len();
         let b = b.mnt_dir.as_os_str().as_bytes().len();
@@ -733,14 +2369,14 @@ fn get_mount_points() -> Result<Vec<MountPoint>, Error> {
             break;
         }
         let dir = unsafe { CStr::from_ptr((*mntent).mnt_dir).to_str().unwrap() };
-        if dir.bytes().len() == 0 {
+        if dir.is_empty() {
             continue;
         }
         let mount_point = unsafe {
             MountPoint {
                 mnt_dir: dir.into(),
-                _mnt_fsname: CStr::from_ptr((*mntent).mnt_fsname).to_str().unwrap().into(),
-                _mnt_type: CStr::from_ptr((*mntent).mnt_type).to_str().unwrap().into(),
+                mnt_fsname: CStr::from_ptr((*mntent).mnt_fsname).to_str().unwrap().into(),
+                mnt_type: CStr::from_ptr((*mntent).mnt_type).to_str().unwrap().into(),
             }
         };
         result.push(mount_point);
@@ -801,7 +2437,7 @@ fn get_mount_points() -> Result<Vec<MountPoint>, Error> {
         let mount_from = c_buf_to_str(&fs_info.f_mntfromname).unwrap_or_default();
 
         let mount_point =
-            MountPoint { mnt_dir: mount_to.into(), _mnt_fsname: mount_from.into(), _mnt_type: fs_type.into() };
+            MountPoint { mnt_dir: mount_to.into(), mnt_fsname: mount_from.into(), mnt_type: fs_type.into() };
         result.push(mount_point);
     }
     Ok(result)
@@ -856,7 +2492,7 @@ fn get_mount_points() -> Result<Vec<MountPoint>, Error> {
         let mount_from = c_buf_to_str(&fs_info.f_mntfromname).unwrap_or_default();
 
         let mount_point =
-            MountPoint { mnt_dir: mount_to.into(), _mnt_fsname: mount_from.into(), _mnt_type: fs_type.into() };
+            MountPoint { mnt_dir: mount_to.into(), mnt_fsname: mount_from.into(), mnt_type: fs_type.into() };
         result.push(mount_point);
     }
     Ok(result)
@@ -874,6 +2510,10 @@ fn get_mount_points() -> Result<Vec<MountPoint>, Error> {
     Err(Error::Unknown { description: "Mount points cannot be determined on this operating system".into() })
 }
 
+fn fs_error(path: impl Into<PathBuf>, source: std::io::Error) -> Error {
+    Error::FileSystem { path: path.into(), source }
+}
+
 #[cfg(test)]
 mod tests {
     use serial_test::serial;
@@ -893,12 +2533,13 @@ mod tests {
     use crate::{
         canonicalize_paths, delete, delete_all,
         os_limited::{list, purge_all, restore_all},
-        platform::encode_uri_path,
+        platform::{encode_uri_path, TopdirPolicy, TrashContextExtFreedesktop},
+        testing::TrashWorld,
         tests::get_unique_name,
-        Error,
+        Error, ListError, TrashContext,
     };
 
-    use super::decode_uri_path;
+    use super::{decode_uri_path, index_path, read_index, restorable_file_in_trash_from_info_file};
 
     #[test]
     #[serial]
@@ -987,6 +2628,1113 @@ mod tests {
         }
     }
 
+    #[test]
+    fn delete_to_custom_home_trash() {
+        crate::tests::init_logging();
+
+        let tmp = tempfile::tempdir().unwrap();
+        let home_trash = tmp.path().join("Trash");
+        let mut ctx = TrashContext::default();
+        ctx.set_home_trash(Some(home_trash.clone()));
+
+        let name = get_unique_name();
+        let path = tmp.path().join(&name);
+        File::create_new(&path).unwrap();
+        ctx.delete(&path).unwrap();
+
+        assert!(!path.exists());
+        assert!(home_trash.join("files").join(&name).is_file());
+        assert!(home_trash.join("info").join(format!("{name}.trashinfo")).is_file());
+    }
+
+    #[test]
+    fn context_settings_apply_to_all_operations() {
+        crate::tests::init_logging();
+
+        let tmp = tempfile::tempdir().unwrap();
+        let home_trash = tmp.path().join("Trash");
+        let mut ctx = TrashContext::default();
+        ctx.set_home_trash(Some(home_trash.clone()));
+        ctx.set_topdir_policy(TopdirPolicy::HomeOnly);
+
+        let names: Vec<_> = (0..2).map(|_| get_unique_name()).collect();
+        for name in &names {
+            File::create_new(tmp.path().join(name)).unwrap();
+        }
+        ctx.delete_all(names.iter().map(|name| tmp.path().join(name))).unwrap();
+        assert_eq!(ctx.trash_folders().unwrap(), [home_trash].into_iter().collect());
+        assert!(!ctx.is_empty().unwrap());
+
+        let (restore, purge): (Vec<_>, Vec<_>) =
+            ctx.list().unwrap().into_iter().partition(|item| item.name == *names[0]);
+        assert_eq!((restore.len(), purge.len()), (1, 1));
+        ctx.restore_all(restore).unwrap();
+        assert!(tmp.path().join(&names[0]).is_file());
+        ctx.purge_all(purge).unwrap();
+        assert!(ctx.is_empty().unwrap());
+    }
+
+    #[test]
+    fn topdir_selection() {
+        crate::tests::init_logging();
+
+        let mut world = TrashWorld::new().unwrap();
+        let usb = world.add_mount("usb").unwrap();
+        let ctx = world.context();
+        let paths = [world.home().join("in-home"), usb.join("on-usb")];
+        for path in &paths {
+            File::create_new(path).unwrap();
+        }
+        ctx.delete_all(&paths).unwrap();
+
+        assert!(world.home_trash().join("files/in-home").is_file());
+        assert!(world.user_trash(&usb).join("files/on-usb").is_file());
+        assert_eq!(ctx.trash_folders().unwrap(), [world.home_trash(), world.user_trash(&usb)].into_iter().collect());
+        let mut original_paths: Vec<_> = ctx.list().unwrap().iter().map(|item| item.original_path()).collect();
+        original_paths.sort();
+        assert_eq!(original_paths, paths);
+    }
+
+    #[test]
+    fn admin_trash_requires_sticky_bit() {
+        crate::tests::init_logging();
+
+        let mut world = TrashWorld::new().unwrap();
+        let sticky = world.add_mount("sticky").unwrap();
+        let not_sticky = world.add_mount("not-sticky").unwrap();
+        world.create_admin_trash(&sticky, true).unwrap();
+        world.create_admin_trash(&not_sticky, false).unwrap();
+        std::fs::create_dir(world.admin_trash(&sticky)).unwrap();
+        std::fs::create_dir(world.admin_trash(&not_sticky)).unwrap();
+
+        let ctx = world.context();
+        let paths = [sticky.join("file"), not_sticky.join("file")];
+        for path in &paths {
+            File::create_new(path).unwrap();
+        }
+        ctx.delete_all(&paths).unwrap();
+
+        assert!(world.admin_trash(&sticky).join("files/file").is_file());
+        assert!(!world.user_trash(&sticky).exists());
+        assert!(world.user_trash(&not_sticky).join("files/file").is_file());
+        assert!(!world.admin_trash(&not_sticky).join("files").exists());
+    }
+
+    #[test]
+    fn admin_trash_folder_of_user_is_created() {
+        crate::tests::init_logging();
+
+        let mut world = TrashWorld::new().unwrap();
+        let created = world.add_mount("created").unwrap();
+        let blocked = world.add_mount("blocked").unwrap();
+        world.create_admin_trash(&created, true).unwrap();
+        world.create_admin_trash(&blocked, true).unwrap();
+        // A file in place of the folder of the user, so that it can't be created
+        File::create_new(world.admin_trash(&blocked)).unwrap();
+
+        let ctx = world.context();
+        let paths = [created.join("file"), blocked.join("file")];
+        for path in &paths {
+            File::create_new(path).unwrap();
+        }
+        ctx.delete_all(&paths).unwrap();
+
+        assert!(world.admin_trash(&created).join("files/file").is_file());
+        assert!(!world.user_trash(&created).exists());
+        assert!(world.user_trash(&blocked).join("files/file").is_file());
+        let mut trash_folders: Vec<_> = ctx.trash_folders().unwrap().into_iter().collect();
+        trash_folders.sort();
+        assert_eq!(trash_folders, [world.user_trash(&blocked), world.admin_trash(&created)]);
+        assert_eq!(ctx.list().unwrap().len(), 2);
+    }
+
+    #[test]
+    fn home_trash_fallback() {
+        crate::tests::init_logging();
+
+        let mut world = TrashWorld::new().unwrap();
+        let topdir = world.add_mount("data").unwrap();
+        // A file in place of the trash folder, so that the trash folder can't be created
+        File::create_new(world.user_trash(&topdir)).unwrap();
+        let path = topdir.join("file");
+        File::create_new(&path).unwrap();
+
+        let mut ctx = world.context();
+        ctx.set_home_trash_fallback(false);
+        assert!(matches!(ctx.delete(&path), Err(Error::FileSystem { .. })));
+        assert!(path.is_file());
+        assert!(!world.home_trash().exists());
+
+        ctx.set_home_trash_fallback(true);
+        ctx.delete(&path).unwrap();
+        assert!(!path.exists());
+        assert!(world.home_trash().join("files/file").is_file());
+        let items = ctx.list().unwrap();
+        assert_eq!(items.len(), 1);
+        assert_eq!(items[0].original_path(), path);
+    }
+
+    #[test]
+    fn user_trash_folders_are_validated() {
+        use std::os::unix::fs::{chown, symlink, MetadataExt, PermissionsExt};
+        crate::tests::init_logging();
+
+        let mut world = TrashWorld::new().unwrap();
+        let created = world.add_mount("created").unwrap();
+        let foreign = world.add_mount("foreign").unwrap();
+        let linked = world.add_mount("linked").unwrap();
+        let uid = unsafe { libc::getuid() };
+        std::fs::create_dir(world.user_trash(&foreign)).unwrap();
+        if chown(world.user_trash(&foreign), Some(uid + 1), None).is_err() {
+            warn!("Can't change the owner of a file, skipping this test");
+            return;
+        }
+        std::fs::create_dir(world.root().join("elsewhere")).unwrap();
+        symlink(world.root().join("elsewhere"), world.user_trash(&linked)).unwrap();
+
+        let mut ctx = world.context();
+        ctx.set_home_trash_fallback(false);
+        File::create_new(created.join("file")).unwrap();
+        ctx.delete(created.join("file")).unwrap();
+        for folder in [world.user_trash(&created), world.user_trash(&created).join("files")] {
+            assert_eq!(folder.metadata().unwrap().permissions().mode() & 0o777, 0o700);
+            assert_eq!(folder.metadata().unwrap().uid(), uid);
+        }
+        for topdir in [&foreign, &linked] {
+            let path = topdir.join("file");
+            File::create_new(&path).unwrap();
+            assert!(matches!(ctx.delete(&path), Err(Error::FileSystem { .. })));
+            assert!(path.is_file());
+        }
+        assert!(!world.user_trash(&foreign).join("files").exists());
+        assert!(!world.root().join("elsewhere/files").exists());
+        assert_eq!(ctx.trash_folders().unwrap().into_iter().collect::<Vec<_>>(), [world.user_trash(&created)]);
+    }
+
+    #[test]
+    fn home_trash_may_be_a_symlink() {
+        use std::os::unix::fs::{symlink, PermissionsExt};
+        crate::tests::init_logging();
+
+        let world = TrashWorld::new().unwrap();
+        let elsewhere = world.root().join("elsewhere");
+        std::fs::create_dir(&elsewhere).unwrap();
+        std::fs::create_dir_all(world.home_trash().parent().unwrap()).unwrap();
+        symlink(&elsewhere, world.home_trash()).unwrap();
+
+        let ctx = world.context();
+        let path = world.home().join("file");
+        File::create_new(&path).unwrap();
+        ctx.delete(&path).unwrap();
+        assert!(!path.exists());
+        assert!(elsewhere.join("files/file").is_file());
+        assert!(elsewhere.join("info/file.trashinfo").is_file());
+        assert_eq!(elsewhere.join("files").metadata().unwrap().permissions().mode() & 0o777, 0o700);
+        let items = ctx.list().unwrap();
+        assert_eq!(items.len(), 1);
+        assert_eq!(items[0].original_path(), path);
+    }
+
+    #[test]
+    fn only_the_trash_folder_is_private() {
+        use std::os::unix::fs::PermissionsExt;
+        crate::tests::init_logging();
+
+        let world = TrashWorld::new().unwrap();
+        let ctx = world.context();
+        let path = world.home().join("file");
+        File::create_new(&path).unwrap();
+        ctx.delete(&path).unwrap();
+
+        // A directory created with the default permissions
+        std::fs::create_dir(world.root().join("reference")).unwrap();
+        let default_mode = world.root().join("reference").metadata().unwrap().permissions().mode();
+        for ancestor in [world.home().join(".local"), world.home().join(".local/share")] {
+            assert_eq!(ancestor.metadata().unwrap().permissions().mode(), default_mode);
+        }
+        assert_eq!(world.home_trash().metadata().unwrap().permissions().mode() & 0o777, 0o700);
+    }
+
+    #[test]
+    fn restore_to_other_directory() {
+        crate::tests::init_logging();
+
+        let world = TrashWorld::new().unwrap();
+        let ctx = world.context();
+        let path = world.home().join("file");
+        std::fs::write(&path, "content").unwrap();
+        let item = ctx.delete_all_with_info([&path]).unwrap().unwrap().remove(0);
+
+        let destination = world.root().join("destination");
+        assert!(matches!(ctx.restore_to(item.clone(), &destination, None), Err(Error::CouldNotAccess { .. })));
+        std::fs::create_dir(&destination).unwrap();
+        File::create_new(destination.join("file")).unwrap();
+        assert!(matches!(ctx.restore_to(item.clone(), &destination, None), Err(Error::RestoreCollision { .. })));
+        assert!(matches!(
+            ctx.restore_to(item.clone(), &destination, Some(OsStr::new("../x"))),
+            Err(Error::Unknown { .. })
+        ));
+
+        let restored = ctx.restore_to(item, &destination, Some(OsStr::new("renamed"))).unwrap();
+        assert_eq!(restored, destination.join("renamed"));
+        assert_eq!(std::fs::read_to_string(&restored).unwrap(), "content");
+        assert!(!path.exists());
+        assert!(ctx.list().unwrap().is_empty());
+        assert_eq!(std::fs::read_dir(world.home_trash().join("info")).unwrap().count(), 0);
+    }
+
+    #[test]
+    fn directory_size_cache() {
+        crate::tests::init_logging();
+
+        let world = TrashWorld::new().unwrap();
+        let ctx = world.context();
+        let dirs = [world.home().join("first dir"), world.home().join("second")];
+        for dir in &dirs {
+            std::fs::create_dir_all(dir.join("nested")).unwrap();
+            std::fs::write(dir.join("a"), [0; 10]).unwrap();
+            std::fs::write(dir.join("nested/b"), [0; 5]).unwrap();
+        }
+        File::create_new(world.home().join("file")).unwrap();
+        let mut items = ctx.delete_all_with_info(dirs.iter().chain([&world.home().join("file")])).unwrap().unwrap();
+
+        // The size is the disk usage, not the sum of the file sizes
+        let trashed = world.home_trash().join("files/first dir");
+        let usage = disk_usage(&[&trashed, &trashed.join("a"), &trashed.join("nested"), &trashed.join("nested/b")]);
+        let sizes = std::fs::read_to_string(world.home_trash().join("directorysizes")).unwrap();
+        let lines: Vec<_> = sizes.lines().map(|line| line.split(' ').collect::<Vec<_>>()).collect();
+        assert_eq!(lines.len(), 2);
+        assert_eq!(lines[0][0], usage.to_string());
+        assert_eq!(lines[0][2], "first%20dir");
+        assert_eq!(ctx.metadata(&items[0]).unwrap().cached_size, Some(usage));
+        assert_eq!(ctx.metadata(&items[2]).unwrap().cached_size, None);
+
+        // An entry is outdated once the info file is modified
+        let info_file = Path::new(&items[1].id);
+        File::options().append(true).open(info_file).unwrap().set_modified(std::time::UNIX_EPOCH).unwrap();
+        assert_eq!(ctx.metadata(&items[1]).unwrap().cached_size, None);
+
+        ctx.restore_all([items.remove(0)]).unwrap();
+        let sizes = std::fs::read_to_string(world.home_trash().join("directorysizes")).unwrap();
+        assert_eq!(sizes.lines().count(), 1);
+        assert!(sizes.ends_with(" second\n"));
+        ctx.purge_all(&items).unwrap();
+        assert_eq!(std::fs::read_to_string(world.home_trash().join("directorysizes")).unwrap(), "");
+    }
+
+    /// Returns the disk space used by `paths`, like `du -B1` counts it.
+    fn disk_usage(paths: &[&Path]) -> u64 {
+        use std::os::unix::fs::MetadataExt;
+        paths.iter().map(|path| path.symlink_metadata().unwrap().blocks() * 512).sum()
+    }
+
+    /// Returns a temporary directory on a different file system than `path`, if there is one.
+    fn tempdir_on_other_file_system(path: &Path) -> Option<tempfile::TempDir> {
+        use std::os::unix::fs::MetadataExt;
+        let dir = tempfile::tempdir_in("/dev/shm").ok()?;
+        let is_other = dir.path().metadata().ok()?.dev() != path.metadata().ok()?.dev();
+        if !is_other {
+            warn!("'/dev/shm' is on the same file system as {:?}, skipping this test", path);
+        }
+        is_other.then_some(dir)
+    }
+
+    #[test]
+    fn delete_across_file_systems() {
+        use std::os::unix::fs::{MetadataExt, PermissionsExt};
+        crate::tests::init_logging();
+
+        let world = TrashWorld::new().unwrap();
+        let Some(other_fs) = tempdir_on_other_file_system(world.root()) else { return };
+        let dir = other_fs.path().join("dir");
+        std::fs::create_dir_all(dir.join("nested")).unwrap();
+        std::fs::write(dir.join("nested/file"), b"content").unwrap();
+        std::fs::set_permissions(dir.join("nested/file"), std::fs::Permissions::from_mode(0o640)).unwrap();
+        unix::fs::symlink("nested/file", dir.join("link")).unwrap();
+        let mtime = |path: &Path| path.symlink_metadata().map(|m| (m.mtime(), m.mtime_nsec())).unwrap();
+        let times = [mtime(&dir), mtime(&dir.join("nested/file")), mtime(&dir.join("link"))];
+
+        let mut ctx = world.context();
+        ctx.set_topdir_policy(TopdirPolicy::HomeOnly);
+        ctx.delete(&dir).unwrap();
+
+        assert!(!dir.exists());
+        let trashed = world.home_trash().join("files/dir");
+        assert_eq!(std::fs::read(trashed.join("nested/file")).unwrap(), b"content");
+        assert_eq!(trashed.join("nested/file").metadata().unwrap().permissions().mode() & 0o777, 0o640);
+        assert_eq!(std::fs::read_link(trashed.join("link")).unwrap(), Path::new("nested/file"));
+        assert_eq!(times, [mtime(&trashed), mtime(&trashed.join("nested/file")), mtime(&trashed.join("link"))]);
+
+        ctx.restore_all(ctx.list().unwrap()).unwrap();
+        assert!(!trashed.exists());
+        assert_eq!(std::fs::read(dir.join("nested/file")).unwrap(), b"content");
+        assert_eq!(dir.join("nested/file").metadata().unwrap().permissions().mode() & 0o777, 0o640);
+        assert_eq!(std::fs::read_link(dir.join("link")).unwrap(), Path::new("nested/file"));
+        assert_eq!(times, [mtime(&dir), mtime(&dir.join("nested/file")), mtime(&dir.join("link"))]);
+        assert!(ctx.is_empty().unwrap());
+    }
+
+    #[test]
+    fn failed_copy_across_file_systems_is_rolled_back() {
+        crate::tests::init_logging();
+
+        let world = TrashWorld::new().unwrap();
+        let Some(other_fs) = tempdir_on_other_file_system(world.root()) else { return };
+        let dir = other_fs.path().join("dir");
+        std::fs::create_dir(&dir).unwrap();
+        File::create_new(dir.join("file")).unwrap();
+        // Named pipes can't be copied
+        let fifo = std::ffi::CString::new(dir.join("fifo").into_os_string().into_vec()).unwrap();
+        assert_eq!(unsafe { libc::mkfifo(fifo.as_ptr(), 0o600) }, 0);
+
+        let mut ctx = world.context();
+        ctx.set_topdir_policy(TopdirPolicy::HomeOnly);
+        assert!(ctx.delete(&dir).is_err());
+
+        assert!(dir.join("file").is_file());
+        assert_eq!(std::fs::read_dir(world.home_trash().join("files")).unwrap().count(), 0);
+        assert_eq!(std::fs::read_dir(world.home_trash().join("info")).unwrap().count(), 0);
+
+        // The same applies to restoring
+        std::fs::remove_file(dir.join("fifo")).unwrap();
+        ctx.delete(&dir).unwrap();
+        let trashed = world.home_trash().join("files/dir");
+        let fifo = std::ffi::CString::new(trashed.join("fifo").into_os_string().into_vec()).unwrap();
+        assert_eq!(unsafe { libc::mkfifo(fifo.as_ptr(), 0o600) }, 0);
+        assert!(ctx.restore_all(ctx.list().unwrap()).is_err());
+
+        assert!(!dir.exists());
+        assert!(trashed.join("file").is_file());
+        assert_eq!(ctx.list().unwrap().len(), 1);
+    }
+
+    #[test]
+    fn transactional_restore() {
+        use crate::{ConflictStrategy, RestoreOptions};
+        crate::tests::init_logging();
+
+        let world = TrashWorld::new().unwrap();
+        let ctx = world.context();
+        let paths = [world.home().join("a"), world.home().join("b")];
+        for path in &paths {
+            std::fs::write(path, "trashed").unwrap();
+        }
+        let items = ctx.delete_all_with_info(&paths).unwrap().unwrap();
+        std::fs::write(&paths[1], "new").unwrap();
+
+        let options = RestoreOptions::new().transactional(true);
+        assert!(matches!(ctx.restore_all_with_options(items.clone(), &options), Err(Error::RestoreCollision { .. })));
+        assert!(!paths[0].exists());
+        assert_eq!(ctx.list().unwrap().len(), 2);
+
+        let options = options.conflict(ConflictStrategy::Rename);
+        ctx.restore_all_with_options(items, &options).unwrap();
+        assert_eq!(std::fs::read_to_string(&paths[0]).unwrap(), "trashed");
+        assert_eq!(std::fs::read_to_string(world.home().join("b (restored)")).unwrap(), "trashed");
+        assert!(ctx.is_empty().unwrap());
+    }
+
+    #[test]
+    fn transactional_restore_checks_before_overwriting() {
+        use crate::{ConflictStrategy, RestoreOptions};
+        use std::os::unix::fs::PermissionsExt;
+        crate::tests::init_logging();
+
+        let world = TrashWorld::new().unwrap();
+        let ctx = world.context();
+        let paths = [world.home().join("a"), world.home().join("dir/b"), world.home().join("read-only/c")];
+        for path in &paths {
+            std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+            std::fs::write(path, "trashed").unwrap();
+        }
+        let items = ctx.delete_all_with_info(&paths).unwrap().unwrap();
+        std::fs::write(&paths[0], "new").unwrap();
+        std::fs::remove_dir(world.home().join("dir")).unwrap();
+        File::create_new(world.home().join("dir")).unwrap();
+
+        // The parent of `b` is a file, which fails before the existing `a` is overwritten, so the
+        // trash isn't even touched
+        let info_folder = world.home_trash().join("info");
+        let modified = info_folder.metadata().unwrap().modified().unwrap();
+        let options = RestoreOptions::new().transactional(true).conflict(ConflictStrategy::Overwrite);
+        let result = ctx.restore_all_with_options(items[..2].to_vec(), &options);
+        assert!(matches!(result, Err(Error::FileSystem { .. })), "{result:?}");
+        assert_eq!(std::fs::read_to_string(&paths[0]).unwrap(), "new");
+        assert_eq!(info_folder.metadata().unwrap().modified().unwrap(), modified);
+        assert_eq!(ctx.list().unwrap().len(), 3);
+
+        // The parent of `c` exists, but items can't be created in it
+        let read_only = world.home().join("read-only");
+        std::fs::set_permissions(&read_only, std::fs::Permissions::from_mode(0o555)).unwrap();
+        if super::is_writable_dir(&read_only) {
+            warn!("Can't make a folder read-only, skipping the rest of this test");
+            return;
+        }
+        let items = [items[0].clone(), items[2].clone()];
+        let result = ctx.restore_all_with_options(items, &options);
+        assert!(matches!(result, Err(Error::FileSystem { .. })), "{result:?}");
+        assert_eq!(std::fs::read_to_string(&paths[0]).unwrap(), "new");
+        assert_eq!(ctx.list().unwrap().len(), 3);
+    }
+
+    #[test]
+    fn list_iter_pages() {
+        use crate::ListErrorKind;
+        crate::tests::init_logging();
+
+        let world = TrashWorld::new().unwrap();
+        let ctx = world.context();
+        let paths: Vec<_> = (0..5).map(|i| world.home().join(i.to_string())).collect();
+        for path in &paths {
+            File::create_new(path).unwrap();
+        }
+        ctx.delete_all(&paths).unwrap();
+        let broken = world.home_trash().join("info/broken.trashinfo");
+        std::fs::write(&broken, "[Trash Info]\nDeletionDate=2024-01-01T00:00:00\n").unwrap();
+
+        let mut first_page = ctx.list_iter().unwrap();
+        let mut entries: Vec<_> = first_page.by_ref().take(2).collect();
+        assert_eq!(first_page.offset(), 2);
+        entries.extend(ctx.list_iter_from(first_page.offset()).unwrap());
+        assert_eq!(entries.len(), 6);
+        let errors: Vec<_> = entries.iter().filter_map(|entry| entry.as_ref().err()).collect();
+        assert_eq!(errors.len(), 1);
+        assert_eq!(errors[0].path, broken);
+        assert!(matches!(errors[0].kind, ListErrorKind::MissingPath));
+
+        let mut listed: Vec<_> = entries.into_iter().filter_map(Result::ok).map(|item| item.original_path()).collect();
+        listed.sort();
+        assert_eq!(listed, paths);
+        assert_eq!(ctx.list().unwrap().len(), 5);
+        assert_eq!(ctx.list_iter_from(6).unwrap().count(), 0);
+    }
+
+    #[test]
+    fn list_iter_resumes_past_malformed_entries() {
+        crate::tests::init_logging();
+
+        let world = TrashWorld::new().unwrap();
+        let ctx = world.context();
+        let paths: Vec<_> = (0..4).map(|i| world.home().join(i.to_string())).collect();
+        for path in &paths {
+            File::create_new(path).unwrap();
+        }
+        ctx.delete_all(&paths).unwrap();
+        let info_folder = world.home_trash().join("info");
+        std::fs::write(info_folder.join("garbage.trashinfo"), b"\xff\xfe not an info file").unwrap();
+        std::fs::write(info_folder.join("nogroup.trashinfo"), "Path=/nowhere\n").unwrap();
+        std::fs::create_dir(info_folder.join("folder.trashinfo")).unwrap();
+
+        // One entry at a time, starting a new listing for every page
+        let mut offset = 0;
+        let mut entries = Vec::new();
+        loop {
+            let mut page = ctx.list_iter_from(offset).unwrap();
+            let Some(entry) = page.next() else { break };
+            assert!(page.offset() > offset);
+            offset = page.offset();
+            entries.push(entry);
+        }
+        assert_eq!(offset, 7);
+        assert_eq!(entries.iter().filter(|entry| entry.is_err()).count(), 3);
+        let mut listed: Vec<_> = entries.into_iter().filter_map(Result::ok).map(|item| item.original_path()).collect();
+        listed.sort();
+        assert_eq!(listed, paths);
+    }
+
+    #[test]
+    fn list_is_stable_and_sortable() {
+        use crate::{TrashItem, TrashItemSize};
+        crate::tests::init_logging();
+
+        let world = TrashWorld::new().unwrap();
+        let ctx = world.context();
+        let paths: Vec<_> = (0..20).map(|i| world.home().join(format!("{i:02}"))).collect();
+        for path in &paths {
+            std::fs::write(path, path.as_os_str().as_encoded_bytes()).unwrap();
+        }
+        ctx.delete_all(&paths).unwrap();
+
+        let listed: Vec<_> = ctx.list_iter().unwrap().map(Result::unwrap).collect();
+        assert_eq!(ctx.list().unwrap(), listed);
+        let sorted = ctx.list_sorted().unwrap();
+        assert_eq!(sorted.iter().map(TrashItem::original_path).collect::<Vec<_>>(), paths);
+
+        let sizes: Vec<_> = ctx.metadata_all(&sorted).into_iter().map(|metadata| metadata.unwrap().size).collect();
+        let expected: Vec<_> = paths.iter().map(|path| TrashItemSize::Bytes(path.as_os_str().len() as u64)).collect();
+        assert_eq!(sizes, expected);
+    }
+
+    #[test]
+    fn trash_info_parsing() {
+        use super::{TrashInfo, TrashInfoError};
+        use crate::ListErrorKind;
+        crate::tests::init_logging();
+
+        let content = b"# comment\n\n[Trash Info]\nPath = rel/a%20b\nX-Custom[de]=Wert\n\n[Other]\nKey=Value\n";
+        let info = TrashInfo::parse(content).unwrap();
+        assert_eq!(info.path, Path::new("rel/a b"));
+        assert_eq!(info.deletion_date, None);
+        assert_eq!(info.other_keys, [("X-Custom[de]".to_owned(), "Wert".to_owned())]);
+        assert_eq!(info.other_groups, [("Other".to_owned(), vec![("Key".to_owned(), "Value".to_owned())])]);
+        let mut written = Vec::new();
+        info.write(&mut written).unwrap();
+        assert_eq!(TrashInfo::parse(&written).unwrap(), info);
+
+        let error = |content: &str| TrashInfo::parse(content.as_bytes()).unwrap_err();
+        assert_eq!(error(""), TrashInfoError::MissingGroup);
+        assert_eq!(error("Path=/a\n[Trash Info]"), TrashInfoError::MissingGroup);
+        assert_eq!(error("[Desktop Entry]\n[Trash Info]\nPath=/a"), TrashInfoError::MissingGroup);
+        assert_eq!(error("[Trash Info]\n\nno separator\n"), TrashInfoError::InvalidLine(3));
+        assert_eq!(error("[Trash Info]\nPath=/a\nPath=/b"), TrashInfoError::Duplicate("Path".into()));
+        assert_eq!(error("[Trash Info]\nDeletionDate=2024-01-01T00:00:00"), TrashInfoError::MissingPath);
+        assert_eq!(
+            error("[Trash Info]\nPath=/a\nDeletionDate=yesterday"),
+            TrashInfoError::InvalidDeletionDate("yesterday".into())
+        );
+        assert_eq!(TrashInfo::parse(b"[Trash Info]\nPath=/a\xff"), Err(TrashInfoError::InvalidUtf8));
+
+        // Malformed info files are reported instead of panicking
+        let world = TrashWorld::new().unwrap();
+        let ctx = world.context();
+        let path = world.home().join("a");
+        File::create_new(&path).unwrap();
+        ctx.delete(&path).unwrap();
+        std::fs::write(world.home_trash().join("info/b.trashinfo"), "[Trash Info]\n\nno separator\n").unwrap();
+        assert_eq!(ctx.list().unwrap().len(), 1);
+        let errors: Vec<_> = ctx.list_iter().unwrap().filter_map(Result::err).collect();
+        assert!(matches!(
+            errors[..],
+            [ListError { kind: ListErrorKind::InvalidInfo(TrashInfoError::InvalidLine(3)), .. }]
+        ));
+    }
+
+    #[test]
+    fn list_with_diagnostics_reports_issues() {
+        use crate::{ListErrorKind, ListIssueReason};
+        crate::tests::init_logging();
+
+        let mut world = TrashWorld::new().unwrap();
+        let usb = world.add_mount("usb").unwrap();
+        std::fs::create_dir(world.user_trash(&usb)).unwrap();
+        let ctx = world.context();
+        let info_folder = world.home_trash().join("info");
+        std::fs::create_dir_all(&info_folder).unwrap();
+        // Written by hand, since there is no deletion date without the "chrono" feature
+        let content = "[Trash Info]\nPath=/a\nDeletionDate=2024-01-01T00:00:00\n";
+        std::fs::write(info_folder.join("a.trashinfo"), content).unwrap();
+        std::fs::write(info_folder.join("no-path.trashinfo"), "[Trash Info]\n").unwrap();
+        std::fs::write(info_folder.join("no-date.trashinfo"), "[Trash Info]\nPath=/b\n").unwrap();
+        std::fs::create_dir(info_folder.join("dir.trashinfo")).unwrap();
+
+        let (items, mut issues) = ctx.list_with_diagnostics().unwrap();
+        assert_eq!(items.len(), 2);
+        issues.sort_by(|a, b| a.path.cmp(&b.path));
+        assert_eq!(issues.len(), 4);
+        let home_trash = Some(world.home_trash());
+        assert_eq!(issues[0].path, info_folder.join("dir.trashinfo"));
+        assert_eq!(issues[0].trash_folder, home_trash);
+        assert!(matches!(issues[0].reason, ListIssueReason::Skipped(ListErrorKind::NotAFile)));
+        assert_eq!(issues[1].path, info_folder.join("no-date.trashinfo"));
+        assert!(matches!(issues[1].reason, ListIssueReason::MissingDeletionDate));
+        assert_eq!(issues[2].path, info_folder.join("no-path.trashinfo"));
+        assert!(matches!(issues[2].reason, ListIssueReason::Skipped(ListErrorKind::MissingPath)));
+        assert_eq!(issues[3].path, world.user_trash(&usb).join("info"));
+        assert_eq!(issues[3].trash_folder, Some(world.user_trash(&usb)));
+        assert!(matches!(issues[3].reason, ListIssueReason::Skipped(ListErrorKind::UnreadableFolder(_))));
+    }
+
+    #[test]
+    fn check_and_repair_trash() {
+        use super::{RepairOptions, TrashProblemKind};
+        use std::os::unix::fs::PermissionsExt;
+        crate::tests::init_logging();
+
+        let world = TrashWorld::new().unwrap();
+        let ctx = world.context();
+        let paths = [world.home().join("file"), world.home().join("dir"), world.home().join("gone")];
+        File::create_new(&paths[0]).unwrap();
+        std::fs::create_dir(&paths[1]).unwrap();
+        File::create_new(&paths[2]).unwrap();
+        ctx.delete_all(&paths).unwrap();
+        assert!(ctx.check_trash().unwrap().is_empty());
+
+        let trash = world.home_trash();
+        std::fs::remove_file(trash.join("files/gone")).unwrap();
+        File::create_new(trash.join("files/orphan")).unwrap();
+        File::create_new(trash.join("files/invalid")).unwrap();
+        std::fs::write(trash.join("info/invalid.trashinfo"), "garbage").unwrap();
+        std::fs::set_permissions(trash.join("files"), std::fs::Permissions::from_mode(0o755)).unwrap();
+        std::fs::write(trash.join("directorysizes"), "5 0 file\n").unwrap();
+
+        let mut kinds: Vec<_> = ctx.check_trash().unwrap().into_iter().map(|problem| problem.kind).collect();
+        kinds.sort_by_key(|kind| format!("{kind:?}"));
+        assert_eq!(kinds.len(), 5, "{kinds:?}");
+        assert!(matches!(&kinds[0], TrashProblemKind::InvalidDirectorySize(name) if name == "file"));
+        assert!(
+            matches!(&kinds[1], TrashProblemKind::InvalidInfo(path, _) if path.ends_with("info/invalid.trashinfo"))
+        );
+        assert!(matches!(&kinds[2], TrashProblemKind::OrphanFile(path) if path.ends_with("files/orphan")));
+        assert!(matches!(&kinds[3], TrashProblemKind::OrphanInfo(path) if path.ends_with("info/gone.trashinfo")));
+        assert!(matches!(&kinds[4], TrashProblemKind::WrongPermissions(path) if *path == trash.join("files")));
+
+        // By default, nothing that may still be of use is touched
+        let outcomes = ctx.repair(&RepairOptions::new()).unwrap();
+        assert_eq!(outcomes.len(), 3);
+        assert!(outcomes.iter().all(|outcome| outcome.error.is_none()), "{outcomes:?}");
+        assert_eq!(ctx.check_trash().unwrap().len(), 2);
+        assert!(std::fs::read_to_string(trash.join("directorysizes")).unwrap().is_empty());
+
+        let recovered = world.home().join("recovered");
+        let options = RepairOptions::new().remove_invalid_info(true).adopt_orphan_files(&recovered);
+        assert_eq!(ctx.repair(&options).unwrap().len(), 3);
+        assert!(ctx.check_trash().unwrap().is_empty());
+        let mut items = ctx.list().unwrap();
+        items.retain(|item| item.original_parent == recovered);
+        assert_eq!(items.len(), 2);
+        ctx.restore_all(items).unwrap();
+        assert!(recovered.join("orphan").is_file() && recovered.join("invalid").is_file());
+    }
+
+    #[test]
+    fn check_trash_follows_a_symlinked_home_trash() {
+        use std::os::unix::fs::{symlink, PermissionsExt};
+        crate::tests::init_logging();
+
+        let world = TrashWorld::new().unwrap();
+        let elsewhere = world.root().join("elsewhere");
+        std::fs::create_dir(&elsewhere).unwrap();
+        std::fs::set_permissions(&elsewhere, std::fs::Permissions::from_mode(0o700)).unwrap();
+        std::fs::create_dir_all(world.home_trash().parent().unwrap()).unwrap();
+        symlink(&elsewhere, world.home_trash()).unwrap();
+
+        let ctx = world.context();
+        let path = world.home().join("file");
+        File::create_new(&path).unwrap();
+        ctx.delete(&path).unwrap();
+        assert!(ctx.check_trash().unwrap().is_empty());
+        assert!(ctx.repair(&super::RepairOptions::new()).unwrap().is_empty());
+    }
+
+    #[test]
+    fn foreign_trash_folders_are_not_repaired() {
+        use super::{RepairOptions, TrashProblemKind};
+        use std::os::unix::fs::{chown, PermissionsExt};
+        crate::tests::init_logging();
+
+        let world = TrashWorld::new().unwrap();
+        let ctx = world.context();
+        let path = world.home().join("file");
+        File::create_new(&path).unwrap();
+        ctx.delete(&path).unwrap();
+        let trash = world.home_trash();
+        let uid = unsafe { libc::getuid() };
+        if chown(trash.join("files"), Some(uid + 1), None).is_err() {
+            warn!("Can't change the owner of a file, skipping this test");
+            return;
+        }
+        std::fs::set_permissions(trash.join("info"), std::fs::Permissions::from_mode(0o755)).unwrap();
+        std::fs::write(trash.join("directorysizes"), "5 0 gone\n").unwrap();
+
+        let mut kinds: Vec<_> = ctx.check_trash().unwrap().into_iter().map(|problem| problem.kind).collect();
+        kinds.sort_by_key(|kind| format!("{kind:?}"));
+        assert_eq!(kinds.len(), 3, "{kinds:?}");
+        assert!(matches!(&kinds[0], TrashProblemKind::InvalidDirectorySize(name) if name == "gone"));
+        assert!(matches!(&kinds[1], TrashProblemKind::WrongOwner(path) if *path == trash.join("files")));
+        assert!(matches!(&kinds[2], TrashProblemKind::WrongPermissions(path) if *path == trash.join("info")));
+
+        // The other problems are still fixed, and the foreign folder is left as it is
+        let outcomes = ctx.repair(&RepairOptions::new()).unwrap();
+        assert_eq!(outcomes.len(), 2, "{outcomes:?}");
+        assert!(outcomes.iter().all(|outcome| outcome.error.is_none()), "{outcomes:?}");
+        let kinds: Vec<_> = ctx.check_trash().unwrap().into_iter().map(|problem| problem.kind).collect();
+        assert!(matches!(&kinds[..], [TrashProblemKind::WrongOwner(_)]), "{kinds:?}");
+    }
+
+    #[test]
+    fn rich_metadata() {
+        use crate::{ExistingKind, TrashItemSize};
+        use std::os::unix::fs::{symlink, MetadataExt};
+        crate::tests::init_logging();
+
+        let world = TrashWorld::new().unwrap();
+        let ctx = world.context();
+        let dir = world.home().join("dir");
+        std::fs::create_dir_all(dir.join("sub")).unwrap();
+        std::fs::write(dir.join("a"), "12345").unwrap();
+        std::fs::write(dir.join("sub/b"), "123").unwrap();
+        let link = world.home().join("link");
+        symlink("target", &link).unwrap();
+        let expected_uid = dir.metadata().unwrap().uid();
+        let items = ctx.delete_all_with_info([&dir, &link]).unwrap().unwrap();
+
+        let metadata = ctx.metadata(&items[0]).unwrap();
+        assert_eq!(metadata.kind, ExistingKind::Directory);
+        assert_eq!(metadata.size, TrashItemSize::Entries(2));
+        assert_eq!(metadata.uid, Some(expected_uid));
+        assert!(metadata.modified.is_some() && metadata.permissions.is_some());
+        assert_eq!((metadata.total_size, metadata.inode_count), (None, None));
+        let deep = ctx.deep_metadata(&items[0]).unwrap();
+        assert_eq!((deep.total_size, deep.inode_count), (Some(8), Some(4)));
+
+        let metadata = ctx.deep_metadata(&items[1]).unwrap();
+        assert_eq!(metadata.kind, ExistingKind::Symlink);
+        assert_eq!(metadata.symlink_target, Some(PathBuf::from("target")));
+        assert_eq!(metadata.inode_count, Some(1));
+
+        // An item that vanished from the trash is an error instead of a panic
+        std::fs::remove_file(restorable_file_in_trash_from_info_file(&items[1].id)).unwrap();
+        assert!(matches!(ctx.metadata(&items[1]), Err(Error::FileSystem { .. })));
+    }
+
+    #[test]
+    fn item_paths_in_trash() {
+        use super::TrashItemExtFreedesktop;
+        use crate::TrashItem;
+        crate::tests::init_logging();
+
+        let mut world = TrashWorld::new().unwrap();
+        let usb = world.add_mount("usb").unwrap();
+        let ctx = world.context();
+        let paths = [world.home().join("a"), usb.join("b")];
+        for path in &paths {
+            File::create_new(path).unwrap();
+        }
+        let items = ctx.delete_all_with_info(&paths).unwrap().unwrap();
+
+        assert_eq!(items[0].trash_folder(), Some(world.home_trash().as_path()));
+        assert_eq!(items[0].topdir(&ctx).unwrap(), world.root());
+        assert_eq!(items[1].trash_folder(), Some(world.user_trash(&usb).as_path()));
+        assert_eq!(items[1].topdir(&ctx).unwrap(), usb);
+        assert_eq!(items[1].info_path(), world.user_trash(&usb).join("info/b.trashinfo"));
+        assert_eq!(items[1].path_in_trash(), Some(world.user_trash(&usb).join("files/b")));
+        assert!(items[1].path_in_trash().unwrap().is_file());
+
+        let item = TrashItem { id: "/".into(), ..items[0].clone() };
+        assert_eq!((item.path_in_trash(), item.trash_folder()), (None, None));
+    }
+
+    #[test]
+    fn list_with_index() {
+        use super::{write_index, IndexEntry, TrashIndex, TrashInfo};
+        use crate::TrashItem;
+        use std::os::unix::fs::PermissionsExt;
+        use std::time::{Duration, SystemTime};
+        crate::tests::init_logging();
+
+        let world = TrashWorld::new().unwrap();
+        let mut ctx = world.context();
+        let index_dir = world.root().join("index");
+        ctx.set_index_dir(Some(index_dir.clone()));
+        let paths: Vec<_> = (0..3).map(|i| world.home().join(i.to_string())).collect();
+        for path in &paths {
+            File::create_new(path).unwrap();
+        }
+        ctx.delete_all(&paths).unwrap();
+        let info_folder = world.home_trash().join("info");
+        let set_info_mtime = |mtime| File::open(&info_folder).unwrap().set_modified(mtime).unwrap();
+        let sorted = |mut items: Vec<TrashItem>| {
+            items.sort_by_key(|item| item.id.clone());
+            items
+        };
+        let unindexed = world.context();
+
+        // A recent mtime of the info folder can't be trusted yet
+        assert_eq!(sorted(ctx.list().unwrap()), sorted(unindexed.list().unwrap()));
+        let index_path = index_path(&index_dir, &world.home_trash());
+        assert_eq!(read_index(&index_path, &world.home_trash()).unwrap().info_mtime, None);
+        let old_mtime = SystemTime::now() - Duration::from_secs(60);
+        set_info_mtime(old_mtime);
+        assert_eq!(ctx.list().unwrap().len(), 3);
+        assert!(read_index(&index_path, &world.home_trash()).unwrap().info_mtime.is_some());
+
+        // The index is used as long as the info folder is unchanged
+        let info_file = PathBuf::from(&ctx.list().unwrap()[0].id);
+        let info = std::fs::read(&info_file).unwrap();
+        std::fs::remove_file(&info_file).unwrap();
+        set_info_mtime(old_mtime);
+        assert_eq!(ctx.list().unwrap().len(), 3);
+
+        // Changes by other tools are noticed
+        std::fs::write(&info_file, info).unwrap();
+        let other_info_file = info_folder.join("other.trashinfo");
+        let mut other_info = TrashInfo::new("/other");
+        other_info.deletion_date = Some("2024-01-01T00:00:00".into());
+        other_info.write(File::create_new(&other_info_file).unwrap()).unwrap();
+        assert_eq!(sorted(ctx.list().unwrap()), sorted(unindexed.list().unwrap()));
+        assert_eq!(ctx.list().unwrap().len(), 4);
+        // The deletion date is kept in local time, as it's converted when listing
+        let index = read_index(&index_path, &world.home_trash()).unwrap();
+        assert!(index.entries.iter().any(|entry| entry.deletion_date.as_deref() == Some("2024-01-01T00:00:00")));
+        std::fs::remove_file(&other_info_file).unwrap();
+        ctx.purge_all(&ctx.list().unwrap()[..1]).unwrap();
+        assert_eq!(sorted(ctx.list().unwrap()), sorted(unindexed.list().unwrap()));
+        assert_eq!(ctx.list().unwrap().len(), 2);
+
+        // A broken index is rebuilt
+        std::fs::write(&index_path, "garbage").unwrap();
+        assert_eq!(sorted(ctx.list().unwrap()), sorted(unindexed.list().unwrap()));
+        assert!(read_index(&index_path, &world.home_trash()).is_some());
+        // The index reveals the original paths, so only the user may read it
+        assert_eq!(index_path.metadata().unwrap().permissions().mode() & 0o777, 0o600);
+
+        // Missing and empty deletion dates are told apart
+        let entry = |name: &str, deletion_date: Option<&str>| IndexEntry {
+            name: name.into(),
+            mtime: (1, 2),
+            len: 3,
+            original_path: world.home().join(name),
+            deletion_date: deletion_date.map(str::to_owned),
+        };
+        let entries = vec![entry("a", None), entry("b", Some("")), entry("c d", Some("2024-01-01T00:00:00"))];
+        let index = TrashIndex { info_mtime: None, entries: entries.clone() };
+        write_index(&index_path, &world.home_trash(), &index).unwrap();
+        assert_eq!(read_index(&index_path, &world.home_trash()).unwrap().entries, entries);
+    }
+
+    #[test]
+    fn restore_outcomes() {
+        use crate::{ConflictStrategy, RestoreOptions};
+        crate::tests::init_logging();
+
+        let world = TrashWorld::new().unwrap();
+        let ctx = world.context();
+        let sub = world.home().join("sub");
+        std::fs::create_dir(&sub).unwrap();
+        let paths = [world.home().join("a"), world.home().join("b"), sub.join("c")];
+        for path in &paths {
+            File::create_new(path).unwrap();
+        }
+        let items = ctx.delete_all_with_info(&paths).unwrap().unwrap();
+        std::fs::remove_dir(&sub).unwrap();
+        std::fs::write(&paths[0], "new").unwrap();
+
+        let options = RestoreOptions::new().continue_on_error(true);
+        let outcomes = ctx.restore_all_with_outcomes(items.clone(), &options).unwrap();
+        assert_eq!(outcomes.iter().map(|outcome| &outcome.item).collect::<Vec<_>>(), items.iter().collect::<Vec<_>>());
+        assert!(matches!(outcomes[0].error, Some(Error::RestoreCollision { .. })));
+        assert_eq!(outcomes[0].path, None);
+        assert_eq!(outcomes[1].path.as_deref(), Some(paths[1].as_path()));
+        assert!(!outcomes[1].created_parents);
+        assert_eq!(outcomes[2].path.as_deref(), Some(paths[2].as_path()));
+        assert!(outcomes[2].created_parents);
+        assert!(paths[1].is_file() && paths[2].is_file());
+        assert_eq!(ctx.list().unwrap(), items[..1]);
+
+        let options = RestoreOptions::new().conflict(ConflictStrategy::Rename).transactional(true);
+        let outcomes = ctx.restore_all_with_outcomes(items[..1].to_vec(), &options).unwrap();
+        assert_eq!(outcomes[0].path, Some(world.home().join("a (restored)")));
+        assert!(outcomes[0].error.is_none());
+        assert!(ctx.is_empty().unwrap());
+    }
+
+    #[test]
+    fn failed_transactional_restore_is_rolled_back() {
+        use crate::RestoreOptions;
+        crate::tests::init_logging();
+
+        let world = TrashWorld::new().unwrap();
+        let Some(other_fs) = tempdir_on_other_file_system(world.root()) else { return };
+        let sub = world.home().join("sub");
+        std::fs::create_dir(&sub).unwrap();
+        let dir = other_fs.path().join("dir");
+        std::fs::create_dir(&dir).unwrap();
+        let paths = [sub.join("a"), sub.join("b"), dir.clone()];
+        for path in &paths[..2] {
+            File::create_new(path).unwrap();
+        }
+        let mut ctx = world.context();
+        ctx.set_topdir_policy(TopdirPolicy::HomeOnly);
+        let items = ctx.delete_all_with_info(&paths).unwrap().unwrap();
+        std::fs::remove_dir(&sub).unwrap();
+        // Named pipes can't be copied, so restoring the last item fails
+        let fifo =
+            std::ffi::CString::new(world.home_trash().join("files/dir/fifo").into_os_string().into_vec()).unwrap();
+        assert_eq!(unsafe { libc::mkfifo(fifo.as_ptr(), 0o600) }, 0);
+
+        let options = RestoreOptions::new().transactional(true);
+        assert!(matches!(ctx.restore_all_with_options(items.clone(), &options), Err(Error::FileSystem { .. })));
+        assert!(!sub.exists());
+        assert!(!dir.exists());
+        assert_eq!(ctx.list().unwrap().len(), 3);
+        for item in &items {
+            assert!(super::restorable_file_in_trash_from_info_file(&item.id).symlink_metadata().is_ok());
+        }
+    }
+
+    #[test]
+    fn transactional_restore_keeps_unremovable_info_files() {
+        use super::TrashProblemKind;
+        use crate::{RestoreOptions, TrashItem};
+        crate::tests::init_logging();
+
+        let world = TrashWorld::new().unwrap();
+        let ctx = world.context();
+        let paths = [world.home().join("a"), world.home().join("b")];
+        for path in &paths {
+            File::create_new(path).unwrap();
+        }
+        let mut items = ctx.delete_all_with_info(&paths).unwrap().unwrap();
+        // An info file that is a directory can't be removed like a file
+        let info_dir = world.home_trash().join("info/c.trashinfo");
+        std::fs::create_dir(&info_dir).unwrap();
+        File::create_new(info_dir.join("entry")).unwrap();
+        File::create_new(world.home_trash().join("files/c")).unwrap();
+        items.push(TrashItem {
+            id: info_dir.clone().into(),
+            name: "c".into(),
+            original_parent: world.home(),
+            time_deleted: -1,
+        });
+
+        let options = RestoreOptions::new().transactional(true);
+        ctx.restore_all_with_options(items, &options).unwrap();
+        for name in ["a", "b", "c"] {
+            assert!(world.home().join(name).is_file());
+        }
+        assert!(!world.home_trash().join("info/a.trashinfo").exists());
+        assert!(info_dir.is_dir());
+        let problems = ctx.check_trash().unwrap();
+        assert!(problems.iter().any(|problem| matches!(&problem.kind, TrashProblemKind::InvalidInfo(path, _) | TrashProblemKind::OrphanInfo(path) if *path == info_dir)));
+    }
+
+    #[test]
+    fn copies_are_verified_by_content() {
+        use super::verify_copy;
+        crate::tests::init_logging();
+
+        let dir = tempfile::tempdir().unwrap();
+        let (src, dst) = (dir.path().join("src"), dir.path().join("dst"));
+        std::fs::create_dir_all(src.join("nested")).unwrap();
+        std::fs::create_dir_all(dst.join("nested")).unwrap();
+        let content: Vec<u8> = (0..200_000).map(|i| i as u8).collect();
+        std::fs::write(src.join("nested/file"), &content).unwrap();
+        std::fs::write(dst.join("nested/file"), &content).unwrap();
+        verify_copy(&src, &dst).unwrap();
+
+        // Same length, but corrupted after the first chunk
+        let mut corrupted = content.clone();
+        corrupted[100_000] ^= 1;
+        std::fs::write(dst.join("nested/file"), &corrupted).unwrap();
+        let (path, error) = verify_copy(&src, &dst).unwrap_err();
+        assert_eq!(path, dst.join("nested/file"));
+        assert_eq!(error.kind(), std::io::ErrorKind::InvalidData);
+
+        // A different kind of item
+        std::fs::remove_file(dst.join("nested/file")).unwrap();
+        std::fs::create_dir(dst.join("nested/file")).unwrap();
+        assert!(verify_copy(&src, &dst).is_err());
+    }
+
+    #[test]
+    fn plan_restore_reports_every_problem() {
+        use crate::{ExistingKind, RestoreStatus};
+        crate::tests::init_logging();
+
+        let world = TrashWorld::new().unwrap();
+        let ctx = world.context();
+        let home = world.home();
+        let names = ["file", "dir", "link", "ready", "twin", "twin", "missing/nested/item", "blocked/item"];
+        let mut items = Vec::new();
+        for name in names {
+            let path = home.join(name);
+            std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+            File::create_new(&path).unwrap();
+            items.extend(ctx.delete_all_with_info([path]).unwrap().unwrap());
+        }
+        File::create_new(home.join("file")).unwrap();
+        std::fs::create_dir(home.join("dir")).unwrap();
+        unix::fs::symlink("nowhere", home.join("link")).unwrap();
+        std::fs::remove_dir_all(home.join("missing")).unwrap();
+        std::fs::remove_dir(home.join("blocked")).unwrap();
+        File::create_new(home.join("blocked")).unwrap();
+
+        let plan = ctx.plan_restore(items);
+        let statuses: Vec<_> = plan.items.iter().map(|planned| planned.status.clone()).collect();
+        assert_eq!(
+            statuses,
+            [
+                RestoreStatus::Collision(ExistingKind::File),
+                RestoreStatus::Collision(ExistingKind::Directory),
+                RestoreStatus::Collision(ExistingKind::Symlink),
+                RestoreStatus::Ready,
+                RestoreStatus::Ready,
+                RestoreStatus::Twin,
+                RestoreStatus::NeedsParent,
+                RestoreStatus::ParentNotDirectory(home.join("blocked")),
+            ]
+        );
+        assert!(!plan.is_ok());
+        assert_eq!(plan.problems().count(), 5);
+        assert_eq!(ctx.list().unwrap().len(), names.len());
+    }
+
+    #[test]
+    fn restore_merging_directories() {
+        use crate::{ConflictStrategy, RestoreOptions};
+        crate::tests::init_logging();
+
+        let world = TrashWorld::new().unwrap();
+        let ctx = world.context();
+        let project = world.home().join("project");
+        let create_trashed = || {
+            std::fs::create_dir_all(project.join("sub")).unwrap();
+            std::fs::write(project.join("a"), "trashed").unwrap();
+            std::fs::write(project.join("b"), "trashed").unwrap();
+            std::fs::write(project.join("sub/c"), "trashed").unwrap();
+            ctx.delete(&project).unwrap();
+            std::fs::create_dir_all(project.join("sub")).unwrap();
+            std::fs::write(project.join("b"), "new").unwrap();
+            std::fs::write(project.join("sub/d"), "new").unwrap();
+        };
+        let read = |name: &str| std::fs::read_to_string(project.join(name)).unwrap();
+
+        create_trashed();
+        let skip = RestoreOptions::new().merge_directories(ConflictStrategy::Skip);
+        ctx.restore_all_with_options(ctx.list().unwrap(), &skip).unwrap();
+        assert_eq!([read("a"), read("b"), read("sub/c"), read("sub/d")], ["trashed", "new", "trashed", "new"]);
+        // The skipped entry keeps the item in the trash
+        let items = ctx.list().unwrap();
+        assert_eq!(items.len(), 1);
+        let trashed = world.home_trash().join("files/project");
+        assert_eq!(ctx.metadata(&items[0]).unwrap().cached_size, Some(disk_usage(&[&trashed, &trashed.join("b")])));
+        assert!(world.home_trash().join("files/project/b").is_file());
+        assert!(!world.home_trash().join("files/project/sub").exists());
+
+        let rename = RestoreOptions::new().merge_directories(ConflictStrategy::Rename);
+        ctx.restore_all_with_options(items, &rename).unwrap();
+        assert_eq!([read("b"), read("b (restored)")], ["new", "trashed"]);
+        assert!(ctx.is_empty().unwrap());
+        assert_eq!(std::fs::read_dir(world.home_trash().join("info")).unwrap().count(), 0);
+
+        // Without merging, the conflict strategy applies to the directory as a whole
+        std::fs::remove_dir_all(&project).unwrap();
+        create_trashed();
+        // Only a nested entry conflicts, so entries that are read before it would be merged already
+        std::fs::remove_file(project.join("b")).unwrap();
+        std::fs::write(project.join("sub/c"), "new").unwrap();
+        let extra_names: Vec<_> = (0..8).map(|i| format!("extra {i}")).collect();
+        for name in &extra_names {
+            std::fs::write(world.home_trash().join("files/project").join(name), "trashed").unwrap();
+        }
+        let fail = RestoreOptions::new().merge_directories(ConflictStrategy::Fail);
+        assert!(matches!(
+            ctx.restore_all_with_options(ctx.list().unwrap(), &fail),
+            Err(Error::RestoreCollision { path, .. }) if path == project.join("sub/c")
+        ));
+        // The conflict is found before anything is merged
+        assert_eq!(ctx.list().unwrap().len(), 1);
+        let mut names: Vec<_> = ["a", "b"].into_iter().chain(extra_names.iter().map(String::as_str)).collect();
+        assert!(names.iter().all(|name| !project.join(name).exists()));
+        let rename = RestoreOptions::new().conflict(ConflictStrategy::Rename);
+        ctx.restore_all_with_options(ctx.list().unwrap(), &rename).unwrap();
+        let restored = world.home().join("project (restored)");
+        names.push("sub/c");
+        for name in names {
+            assert_eq!(std::fs::read_to_string(restored.join(name)).unwrap(), "trashed");
+        }
+    }
+
     #[test]
     fn uri_enc_dec_roundtrip() {
         let fake = format!("/tmp/{}", get_unique_name());
@@ -1022,7 +3770,7 @@ mod tests {
     #[derive(Debug)]
     pub enum SystemTrashError {
         NoTrashProgram,
-        Other(Error),
+        Other(#[allow(dead_code)] Error),
     }
     impl fmt::Display for SystemTrashError {
         fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
@@ -1210,7 +3958,3 @@ mod tests {
         DesktopEnvironment::Other
     }
 }
-
-fn fs_error(path: impl Into<PathBuf>, source: std::io::Error) -> Error {
-    Error::FileSystem { path: path.into(), source }
-}
@@ -32,6 +32,7 @@
 use std::ffi::OsString;
 use std::hash::{Hash, Hasher};
 use std::path::{Path, PathBuf};
+use std::sync::Arc;
 
 use std::fmt;
 use std::{env::current_dir, error};
@@ -41,13 +42,30 @@ use log::trace;
 #[cfg(test)]
 pub mod tests;
 
+pub mod backend;
+use backend::TrashBackend;
+
+#[cfg(feature = "tokio")]
+pub mod asynchronous;
+
 #[cfg(target_os = "windows")]
 #[path = "windows.rs"]
 mod platform;
 
 #[cfg(all(unix, not(target_os = "macos"), not(target_os = "ios"), not(target_os = "android")))]
 #[path = "freedesktop.rs"]
-mod platform;
+pub mod freedesktop;
+#[cfg(all(unix, not(target_os = "macos"), not(target_os = "ios"), not(target_os = "android")))]
+use freedesktop as platform;
+
+#[cfg(all(
+    any(test, feature = "testing"),
+    unix,
+    not(target_os = "macos"),
+    not(target_os = "ios"),
+    not(target_os = "android")
+))]
+pub mod testing;
 
 #[cfg(target_os = "macos")]
 #[path = "macos/macos.rs"]
@@ -60,12 +78,28 @@ pub const DEFAULT_TRASH_CTX: TrashContext = TrashContext::new();
 /// A collection of preferences for trash operations.
 #[derive(Clone, Default, Debug)]
 pub struct TrashContext {
-    #[cfg_attr(not(target_os = "macos"), allow(dead_code))]
     platform_specific: platform::PlatformTrashContext,
+    backend: Option<Arc<dyn TrashBackend>>,
 }
 impl TrashContext {
     pub const fn new() -> Self {
-        Self { platform_specific: platform::PlatformTrashContext::new() }
+        Self { platform_specific: platform::PlatformTrashContext::new(), backend: None }
+    }
+
+    /// Creates a context that performs every operation through `backend` instead of the trash
+    /// of the operating system.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use trash::{backend::MemoryBackend, TrashContext};
+    /// let backend = MemoryBackend::new();
+    /// let ctx = TrashContext::with_backend(backend.clone());
+    /// ctx.delete("Cargo.toml").unwrap();
+    /// assert_eq!(backend.items().len(), 1);
+    /// ```
+    pub fn with_backend(backend: impl TrashBackend + 'static) -> Self {
+        Self { backend: Some(Arc::new(backend)), ..Self::new() }
     }
 
     /// Removes a single file or directory.
@@ -122,7 +156,7 @@ impl TrashContext {
         trace!("Starting canonicalize_paths");
         let full_paths = canonicalize_paths(paths)?;
         trace!("Finished canonicalize_paths");
-        self.delete_all_canonicalized(full_paths, false)
+        self.delete_canonicalized(full_paths, false)
     }
 
     /// Same as `delete_all, but returns `TrashItem`s if available.
@@ -134,7 +168,18 @@ impl TrashContext {
         trace!("Starting canonicalize_paths");
         let full_paths = canonicalize_paths(paths)?;
         trace!("Finished canonicalize_paths");
-        self.delete_all_canonicalized(full_paths, true)
+        self.delete_canonicalized(full_paths, true)
+    }
+
+    pub(crate) fn delete_canonicalized(
+        &self,
+        full_paths: Vec<PathBuf>,
+        with_info: bool,
+    ) -> Result<Option<Vec<TrashItem>>, Error> {
+        match &self.backend {
+            Some(backend) => backend.delete_all(full_paths, with_info),
+            None => self.delete_all_canonicalized(full_paths, with_info),
+        }
     }
 }
 
@@ -395,10 +440,389 @@ impl TrashItemSize {
 }
 
 /// Metadata about a [`TrashItem`]
-#[derive(Debug, Clone, Copy, Ord, PartialOrd, Eq, PartialEq, Hash)]
+///
+/// The fields that are `None` are not known for the item, or not supported by the platform.
+/// More fields may be added in the future, use [`TrashItemMetadata::new`] to create one.
+#[derive(Debug, Clone, Ord, PartialOrd, Eq, PartialEq, Hash)]
+#[non_exhaustive]
 pub struct TrashItemMetadata {
     /// The size of the item, depending on whether or not it is a directory.
     pub size: TrashItemSize,
+    /// The disk space used by a directory in bytes, if it's known without walking the directory.
+    ///
+    /// On Freedesktop this is read from the `directorysizes` cache of the trash folder, which is
+    /// kept up to date when directories are moved to the trash, purged or restored.
+    pub cached_size: Option<u64>,
+    /// What kind of item it is. Symlinks are not followed.
+    pub kind: ExistingKind,
+    /// The path a symlink points to.
+    pub symlink_target: Option<PathBuf>,
+    /// The time the item was last modified, in seconds since the UNIX epoch.
+    pub modified: Option<i64>,
+    /// The Unix permission bits, like `0o644`.
+    pub permissions: Option<u32>,
+    /// The user id of the owner.
+    pub uid: Option<u32>,
+    /// The group id of the owner.
+    pub gid: Option<u32>,
+    /// The total size in bytes of the files in the item and all of its subdirectories.
+    ///
+    /// Only set by [`TrashContext::deep_metadata`], which walks the whole item.
+    pub total_size: Option<u64>,
+    /// The number of files and directories the item consists of, including the item itself.
+    ///
+    /// Only set by [`TrashContext::deep_metadata`], which walks the whole item.
+    pub inode_count: Option<u64>,
+}
+
+impl TrashItemMetadata {
+    /// The metadata of an item of the given `kind` of which nothing but the `size` is known.
+    ///
+    /// The other fields can be set afterwards, this is mostly useful for implementing a
+    /// [`TrashBackend`].
+    pub fn new(size: TrashItemSize, kind: ExistingKind) -> Self {
+        Self {
+            size,
+            cached_size: None,
+            kind,
+            symlink_target: None,
+            modified: None,
+            permissions: None,
+            uid: None,
+            gid: None,
+            total_size: None,
+            inode_count: None,
+        }
+    }
+
+    /// The metadata of the item at `path` as read from the file system.
+    pub(crate) fn from_fs(path: &Path, metadata: &std::fs::Metadata, size: TrashItemSize) -> Self {
+        let kind = ExistingKind::from(metadata.file_type());
+        let mut result = Self::new(size, kind);
+        if kind == ExistingKind::Symlink {
+            result.symlink_target = std::fs::read_link(path).ok();
+        }
+        result.modified = metadata
+            .modified()
+            .ok()
+            .and_then(|modified| modified.duration_since(std::time::UNIX_EPOCH).ok())
+            .map(|modified| modified.as_secs() as i64);
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::MetadataExt;
+            result.permissions = Some(metadata.mode() & 0o7777);
+            result.uid = Some(metadata.uid());
+            result.gid = Some(metadata.gid());
+        }
+        result
+    }
+}
+
+impl From<std::fs::FileType> for ExistingKind {
+    fn from(file_type: std::fs::FileType) -> Self {
+        if file_type.is_symlink() {
+            ExistingKind::Symlink
+        } else if file_type.is_dir() {
+            ExistingKind::Directory
+        } else if file_type.is_file() {
+            ExistingKind::File
+        } else {
+            ExistingKind::Other
+        }
+    }
+}
+
+/// What to do with an item that can't be restored because its original path is taken.
+///
+/// The original path may be taken by an unrelated item, or by another item that was restored
+/// before it in the same call, when multiple items share the same original path.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub enum ConflictStrategy {
+    /// Stop with [`Error::RestoreCollision`] or [`Error::RestoreTwins`], like
+    /// [`restore_all`](TrashContext::restore_all) does.
+    #[default]
+    Fail,
+    /// Leave the item in the trash and continue with the next one.
+    Skip,
+    /// Move the item that takes the original path to the trash, then restore the item.
+    Overwrite,
+    /// Restore the item under a new name, like `name (restored).ext` or `name (restored 2).ext`.
+    Rename,
+}
+
+/// Options for [`TrashContext::restore_all_with_options`].
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct RestoreOptions {
+    /// What to do when the original path of an item is taken.
+    pub conflict: ConflictStrategy,
+    /// Restore either all items or none of them.
+    ///
+    /// Every item is checked before anything is restored, and if restoring an item fails
+    /// nonetheless, the items restored until then are moved back into the trash. Conflicts are
+    /// resolved upfront, based on the items at the original paths at that time. With
+    /// [`ConflictStrategy::Overwrite`], the existing items are only moved to the trash once every
+    /// check passed, and they are restored again if restoring fails.
+    ///
+    /// Once every item is back at its original path, restoring succeeds. On Freedesktop, the info
+    /// files are removed only then, and an info file that can't be removed is left behind with a
+    /// warning in the log. Such an info file is reported as an orphan by `check_trash`.
+    ///
+    /// Rolling back is only supported on Freedesktop and by backends that restore all items at
+    /// once. The Windows recycle bin can't roll back, so restoring with this option returns an
+    /// error there, before anything is restored.
+    pub transactional: bool,
+    /// Merge a trashed directory into the existing directory at its original path, instead of
+    /// treating it as a conflict. Entries that exist in both are merged recursively if both are
+    /// directories, and resolved with the given strategy otherwise.
+    ///
+    /// With [`ConflictStrategy::Fail`], every entry is checked before anything is moved, so a
+    /// [`RestoreCollision`](Error::RestoreCollision) leaves the item untouched in the trash. Any
+    /// other error may occur after some entries were merged already. The merged entries then stay
+    /// at the original path, while the rest of the directory stays in the trash as the same item.
+    ///
+    /// Entries that stay in the trash, because they were skipped or failed to be restored, keep the
+    /// item in the trash. Merging is only supported on Freedesktop and not in transactional mode.
+    /// Backends treat the directory as a conflict, and on Windows restoring with this option
+    /// returns an error before anything is restored.
+    pub merge_directories: Option<ConflictStrategy>,
+    /// Keep restoring the remaining items when an item fails to be restored.
+    ///
+    /// See [`restore_all_with_outcomes`](TrashContext::restore_all_with_outcomes) for how failures
+    /// are reported. Has no effect in transactional mode.
+    pub continue_on_error: bool,
+}
+
+impl RestoreOptions {
+    /// Returns options that behave like [`restore_all`](TrashContext::restore_all).
+    pub const fn new() -> Self {
+        Self {
+            conflict: ConflictStrategy::Fail,
+            transactional: false,
+            merge_directories: None,
+            continue_on_error: false,
+        }
+    }
+
+    /// Returns these options with the given continue-on-error mode.
+    pub const fn continue_on_error(mut self, continue_on_error: bool) -> Self {
+        self.continue_on_error = continue_on_error;
+        self
+    }
+
+    /// Returns these options with merging directories enabled, resolving conflicting entries with
+    /// `entry_conflict`.
+    pub const fn merge_directories(mut self, entry_conflict: ConflictStrategy) -> Self {
+        self.merge_directories = Some(entry_conflict);
+        self
+    }
+
+    /// Returns these options with the given transactional mode.
+    pub const fn transactional(mut self, transactional: bool) -> Self {
+        self.transactional = transactional;
+        self
+    }
+
+    /// Returns these options with the given conflict strategy.
+    pub const fn conflict(mut self, conflict: ConflictStrategy) -> Self {
+        self.conflict = conflict;
+        self
+    }
+}
+
+/// What happened to a [`TrashItem`], as returned by [`TrashContext::restore_all_with_outcomes`].
+#[derive(Debug)]
+pub struct RestoreOutcome {
+    pub item: TrashItem,
+    /// The path the item was restored to, which differs from its original path if it was renamed.
+    /// `None` if the item is still in the trash, because it was skipped or failed to be restored.
+    pub path: Option<PathBuf>,
+    /// Whether missing folders of the original parent were created for the item.
+    pub created_parents: bool,
+    /// Why the item couldn't be restored, only set with [`RestoreOptions::continue_on_error`].
+    pub error: Option<Error>,
+}
+
+/// The kind of an existing item on the file system.
+#[derive(Debug, Clone, Copy, Ord, PartialOrd, PartialEq, Eq, Hash)]
+pub enum ExistingKind {
+    File,
+    Directory,
+    Symlink,
+    /// Anything else, like a named pipe or a device.
+    Other,
+}
+
+/// What is expected to happen when restoring a [`TrashItem`], as determined by
+/// [`TrashContext::plan_restore`].
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum RestoreStatus {
+    /// The item can be restored to its original path.
+    Ready,
+    /// The item can be restored once the missing folders of its original parent are created,
+    /// which [`restore_all`](TrashContext::restore_all) does.
+    NeedsParent,
+    /// The original path is taken by an existing item of the given kind.
+    Collision(ExistingKind),
+    /// An earlier item has the same original path, see [`Error::RestoreTwins`].
+    Twin,
+    /// The original parent can't be created, since the given path that should be one of its
+    /// folders is not a directory.
+    ParentNotDirectory(PathBuf),
+    /// The current user may not create items in the given directory, which is the original parent
+    /// or the closest of its folders that exists.
+    ///
+    /// Never reported on Windows, where access is controlled by ACLs that are only checked when
+    /// restoring.
+    PermissionDenied(PathBuf),
+}
+
+impl RestoreStatus {
+    /// Whether the item is expected to be restored without problems.
+    pub fn is_ok(&self) -> bool {
+        matches!(self, RestoreStatus::Ready | RestoreStatus::NeedsParent)
+    }
+}
+
+/// The expected outcome of restoring a single [`TrashItem`].
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct PlannedRestore {
+    pub item: TrashItem,
+    pub status: RestoreStatus,
+}
+
+/// The expected outcome of restoring a list of [`TrashItem`]s, created by [`TrashContext::plan_restore`].
+///
+/// The plan reflects the file system at the time it was created, so restoring may still fail if
+/// the file system changes in the meantime.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct RestorePlan {
+    /// Every item in the order it was provided.
+    pub items: Vec<PlannedRestore>,
+}
+
+impl RestorePlan {
+    /// Whether every item is expected to be restored without problems.
+    pub fn is_ok(&self) -> bool {
+        self.items.iter().all(|planned| planned.status.is_ok())
+    }
+
+    /// The items that are expected to fail, in the order they were provided.
+    pub fn problems(&self) -> impl Iterator<Item = &PlannedRestore> {
+        self.items.iter().filter(|planned| !planned.status.is_ok())
+    }
+}
+
+/// An entry of the trash that couldn't be turned into a [`TrashItem`] while listing.
+#[derive(Debug)]
+pub struct ListError {
+    /// The entry that couldn't be read, or the folder if the whole folder couldn't be read.
+    pub path: PathBuf,
+    pub kind: ListErrorKind,
+}
+
+/// Why an entry of the trash couldn't be listed, see [`ListError`].
+#[derive(Debug)]
+pub enum ListErrorKind {
+    /// The folder with the entries of a trash folder is missing or couldn't be read.
+    UnreadableFolder(std::io::Error),
+    /// The entry is not a file.
+    NotAFile,
+    /// The entry couldn't be read.
+    Io(std::io::Error),
+    /// The entry doesn't say where the item was deleted from.
+    MissingPath,
+    /// The deletion date of the entry is not valid.
+    InvalidDeletionDate(String),
+    /// **freedesktop only**
+    ///
+    /// The entry is not a valid info file.
+    #[cfg(all(unix, not(target_os = "macos"), not(target_os = "ios"), not(target_os = "android")))]
+    InvalidInfo(freedesktop::TrashInfoError),
+}
+
+impl fmt::Display for ListError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Could not list the trash entry {:?}: {:?}", self.path, self.kind)
+    }
+}
+impl error::Error for ListError {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        match &self.kind {
+            ListErrorKind::UnreadableFolder(source) | ListErrorKind::Io(source) => Some(source),
+            #[cfg(all(unix, not(target_os = "macos"), not(target_os = "ios"), not(target_os = "android")))]
+            ListErrorKind::InvalidInfo(source) => Some(source),
+            _ => None,
+        }
+    }
+}
+
+/// A problem with the trash that was found by [`TrashContext::list_with_diagnostics`].
+///
+/// Issues are only reported for the Freedesktop trash, see the platform behavior of
+/// `list_with_diagnostics`.
+#[derive(Debug)]
+pub struct ListIssue {
+    /// The entry the issue is about, or the folder if the whole folder is affected.
+    pub path: PathBuf,
+    /// The trash folder the entry belongs to, if it's known.
+    pub trash_folder: Option<PathBuf>,
+    pub reason: ListIssueReason,
+}
+
+/// What's wrong with an entry of the trash, see [`ListIssue`].
+#[derive(Debug)]
+pub enum ListIssueReason {
+    /// The entry couldn't be listed, so there is no item for it.
+    Skipped(ListErrorKind),
+    /// The entry doesn't say when it was deleted, so its item was listed with a `time_deleted` of -1.
+    MissingDeletionDate,
+}
+
+/// A lazy iterator over the items in the trash, created by [`TrashContext::list_iter`].
+///
+/// Entries are read one at a time when the iterator is advanced. An entry that can't be read
+/// yields a [`ListError`], and the iteration continues with the next entry.
+pub struct ListIter {
+    /// Yields each result with the offset after it.
+    inner: Box<dyn Iterator<Item = (usize, Result<TrashItem, ListError>)> + Send>,
+    offset: usize,
+}
+
+impl ListIter {
+    #[cfg(any(
+        target_os = "windows",
+        all(unix, not(target_os = "macos"), not(target_os = "ios"), not(target_os = "android"))
+    ))]
+    fn new(inner: Box<dyn Iterator<Item = (usize, Result<TrashItem, ListError>)> + Send>, offset: usize) -> Self {
+        Self { inner, offset }
+    }
+
+    /// The number of entries this iterator has passed, including the ones it started after.
+    ///
+    /// Entries that don't yield anything, like an info file that was removed while listing, are
+    /// counted as well, so the offset always refers to the entries of the trash.
+    ///
+    /// Hand it to [`TrashContext::list_iter_from`] to continue listing from here later on, for
+    /// example to list the trash one page at a time.
+    pub fn offset(&self) -> usize {
+        self.offset
+    }
+}
+
+impl Iterator for ListIter {
+    type Item = Result<TrashItem, ListError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let (offset, next) = self.inner.next()?;
+        self.offset = offset;
+        Some(next)
+    }
+}
+
+impl fmt::Debug for ListIter {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ListIter").field("offset", &self.offset).finish_non_exhaustive()
+    }
 }
 
 #[cfg(any(
@@ -408,196 +832,984 @@ pub struct TrashItemMetadata {
 pub mod os_limited {
     //! This module provides functionality which is only supported on Windows and
     //! Linux or other Freedesktop Trash compliant environment.
+    //!
+    //! Every function here is a convenience wrapper around the method of the same name
+    //! on [`DEFAULT_TRASH_CTX`]. Use a configured [`TrashContext`] to apply its settings.
 
     use std::{
         borrow::Borrow,
         collections::HashSet,
+        env::current_dir,
+        ffi::{OsStr, OsString},
         hash::{Hash, Hasher},
+        path::{Path, PathBuf},
     };
 
-    use super::{platform, Error, TrashItem, TrashItemMetadata};
+    use log::warn;
 
-    /// Returns all [`TrashItem`]s that are currently in the trash.
-    ///
-    /// The items are in no particular order and must be sorted when any kind of ordering is required.
+    use super::{
+        platform, ConflictStrategy, Error, ListIssue, ListIter, PlannedRestore, RestoreOptions, RestoreOutcome,
+        RestorePlan, RestoreStatus, TrashContext, TrashItem, TrashItemMetadata, DEFAULT_TRASH_CTX,
+    };
+
+    impl TrashContext {
+        /// Returns all [`TrashItem`]s that are currently in the trash.
+        ///
+        /// The items are in no particular order and must be sorted when any kind of ordering is required.
+        ///
+        /// # Example
+        ///
+        /// ```
+        /// use trash::os_limited::list;
+        /// let trash_items = list().unwrap();
+        /// println!("{:#?}", trash_items);
+        /// ```
+        pub fn list(&self) -> Result<Vec<TrashItem>, Error> {
+            match &self.backend {
+                Some(backend) => backend.list(),
+                None => platform::list(&self.platform_specific),
+            }
+        }
+
+        /// Same as [`list`](TrashContext::list), but the items are sorted by the time they were
+        /// deleted, and by their original path and id if that's the same.
+        ///
+        /// With the "rayon" feature, the items are sorted on multiple threads.
+        pub fn list_sorted(&self) -> Result<Vec<TrashItem>, Error> {
+            let mut items = self.list()?;
+            let order = |a: &TrashItem, b: &TrashItem| {
+                (a.time_deleted, &a.original_parent, &a.name, &a.id).cmp(&(
+                    b.time_deleted,
+                    &b.original_parent,
+                    &b.name,
+                    &b.id,
+                ))
+            };
+            #[cfg(feature = "rayon")]
+            rayon::slice::ParallelSliceMut::par_sort_by(&mut items[..], order);
+            #[cfg(not(feature = "rayon"))]
+            items.sort_by(order);
+            Ok(items)
+        }
+
+        /// Same as [`list`](TrashContext::list), but also returns the problems that were found
+        /// while listing, like entries that are damaged and therefore skipped.
+        ///
+        /// The problems are reported regardless of the log level.
+        ///
+        /// # Platform behavior
+        ///
+        /// Only the Freedesktop trash reports problems. There, the [`path`](ListIssue::path) of an
+        /// issue is an info file or an `info` folder of a trash folder.
+        ///
+        /// On Windows and for a context with a [`TrashBackend`](crate::backend::TrashBackend),
+        /// this is the same as `list` and the issues are always empty. An empty list of issues
+        /// there doesn't mean that nothing is damaged, since damaged entries are not noticed.
+        ///
+        /// # Example
+        ///
+        /// ```
+        /// use trash::os_limited::list_with_diagnostics;
+        /// let (items, issues) = list_with_diagnostics().unwrap();
+        /// if !issues.is_empty() {
+        ///     println!("{} trash entries are damaged", issues.len());
+        /// }
+        /// println!("{:#?}", items);
+        /// ```
+        pub fn list_with_diagnostics(&self) -> Result<(Vec<TrashItem>, Vec<ListIssue>), Error> {
+            match &self.backend {
+                Some(backend) => Ok((backend.list()?, vec![])),
+                None => platform::list_with_diagnostics(&self.platform_specific),
+            }
+        }
+
+        /// Returns an iterator that reads the items in the trash lazily, one entry at a time.
+        ///
+        /// Unlike [`list`](TrashContext::list), the trash isn't read any further than the iterator
+        /// is advanced, and entries that can't be read are reported instead of being skipped.
+        /// The items are in no particular order, but the order doesn't change as long as the
+        /// trash doesn't.
+        ///
+        /// # Example
+        ///
+        /// ```
+        /// use trash::os_limited::list_iter;
+        /// let first_items: Vec<_> = list_iter().unwrap().filter_map(Result::ok).take(10).collect();
+        /// println!("{:#?}", first_items);
+        /// ```
+        pub fn list_iter(&self) -> Result<ListIter, Error> {
+            self.list_iter_from(0)
+        }
+
+        /// Same as [`list_iter`](TrashContext::list_iter), but starts after the first `offset` entries.
+        ///
+        /// The skipped entries are not parsed. Use [`ListIter::offset`] to get the offset of a
+        /// previous iterator. Items that are added or removed in the meantime may shift the
+        /// entries, so an item may be listed twice or be missed.
+        ///
+        /// # Example
+        ///
+        /// ```
+        /// use trash::os_limited::{list_iter, list_iter_from};
+        /// let mut first_page = list_iter().unwrap();
+        /// let first_items: Vec<_> = first_page.by_ref().take(50).collect();
+        /// let second_items: Vec<_> = list_iter_from(first_page.offset()).unwrap().take(50).collect();
+        /// println!("{} + {}", first_items.len(), second_items.len());
+        /// ```
+        pub fn list_iter_from(&self, offset: usize) -> Result<ListIter, Error> {
+            let inner: Box<dyn Iterator<Item = _> + Send> = match &self.backend {
+                Some(backend) => Box::new(
+                    backend.list()?.into_iter().map(Ok).enumerate().skip(offset).map(|(i, result)| (i + 1, result)),
+                ),
+                None => Box::new(platform::list_iter(&self.platform_specific, offset)?),
+            };
+            Ok(ListIter::new(inner, offset))
+        }
+
+        /// Returns whether the trash is empty or has at least one item.
+        ///
+        /// Unlike calling [`list`](TrashContext::list), this function short circuits without evaluating every item.
+        ///
+        /// # Example
+        ///
+        /// ```
+        /// use trash::os_limited::is_empty;
+        /// if is_empty().unwrap_or(true) {
+        ///     println!("Trash is empty");
+        /// } else {
+        ///     println!("Trash contains at least one item");
+        /// }
+        /// ```
+        pub fn is_empty(&self) -> Result<bool, Error> {
+            match &self.backend {
+                Some(backend) => backend.is_empty(),
+                None => platform::is_empty(&self.platform_specific),
+            }
+        }
+
+        /// Returns all valid trash bins on supported Unix platforms.
+        ///
+        /// Valid trash folders include the user's personal "home trash" as well as designated trash
+        /// bins across mount points. Some, or all of these, may not exist or be invalid in some way.
+        ///
+        /// # Example
+        ///
+        /// ```
+        /// # #[cfg(all(unix, not(target_os = "macos"), not(target_os = "ios"), not(target_os = "android")))] {
+        /// use trash::os_limited::trash_folders;
+        /// let trash_bins = trash_folders()?;
+        /// println!("{trash_bins:#?}");
+        /// # }
+        /// # Ok::<(), trash::Error>(())
+        /// ```
+        #[cfg(all(unix, not(target_os = "macos"), not(target_os = "ios"), not(target_os = "android")))]
+        pub fn trash_folders(&self) -> Result<HashSet<std::path::PathBuf>, Error> {
+            match &self.backend {
+                Some(backend) => backend.trash_folders(),
+                None => platform::trash_folders(&self.platform_specific),
+            }
+        }
+
+        /// Checks the trash folders for problems, like info files without an item and items
+        /// without an info file, which are left behind by crashes or other programs.
+        ///
+        /// A context with a [`TrashBackend`](crate::backend::TrashBackend) returns an error, since it
+        /// has no trash folders to check.
+        ///
+        /// # Example
+        ///
+        /// ```
+        /// # #[cfg(all(unix, not(target_os = "macos"), not(target_os = "ios"), not(target_os = "android")))] {
+        /// use trash::os_limited::check_trash;
+        /// for problem in check_trash()? {
+        ///     println!("{:?}: {:?}", problem.trash_folder, problem.kind);
+        /// }
+        /// # }
+        /// # Ok::<(), trash::Error>(())
+        /// ```
+        #[cfg(all(unix, not(target_os = "macos"), not(target_os = "ios"), not(target_os = "android")))]
+        pub fn check_trash(&self) -> Result<Vec<crate::freedesktop::TrashProblem>, Error> {
+            match &self.backend {
+                Some(_) => Err(crate::backend::unsupported("check_trash")),
+                None => platform::check_trash(&self.platform_specific),
+            }
+        }
+
+        /// Fixes the problems found by [`check_trash`](TrashContext::check_trash) that are enabled
+        /// in `options`, and returns the problems it tried to fix.
+        ///
+        /// A problem that fails to be fixed doesn't stop the others from being fixed, its
+        /// [`RepairOutcome`](crate::freedesktop::RepairOutcome) has the error instead.
+        ///
+        /// Like `check_trash`, this returns an error for a context with a
+        /// [`TrashBackend`](crate::backend::TrashBackend).
+        ///
+        /// # Example
+        ///
+        /// ```no_run
+        /// # #[cfg(all(unix, not(target_os = "macos"), not(target_os = "ios"), not(target_os = "android")))] {
+        /// use trash::{freedesktop::RepairOptions, os_limited::repair};
+        /// let outcomes = repair(&RepairOptions::new().remove_invalid_info(true))?;
+        /// println!("Fixed {} problems", outcomes.iter().filter(|outcome| outcome.error.is_none()).count());
+        /// # }
+        /// # Ok::<(), trash::Error>(())
+        /// ```
+        #[cfg(all(unix, not(target_os = "macos"), not(target_os = "ios"), not(target_os = "android")))]
+        pub fn repair(
+            &self,
+            options: &crate::freedesktop::RepairOptions,
+        ) -> Result<Vec<crate::freedesktop::RepairOutcome>, Error> {
+            match &self.backend {
+                Some(_) => Err(crate::backend::unsupported("repair")),
+                None => platform::repair(&self.platform_specific, options),
+            }
+        }
+
+        /// Returns the [`TrashItemMetadata`] for a [`TrashItem`]
+        ///
+        /// # Example
+        ///
+        /// ```
+        /// use trash::os_limited::{list, metadata};
+        /// let trash_items = list().unwrap();
+        /// for item in trash_items {
+        ///     println!("{:#?}", metadata(&item).unwrap());
+        /// }
+        /// ```
+        pub fn metadata(&self, item: &TrashItem) -> Result<TrashItemMetadata, Error> {
+            match &self.backend {
+                Some(backend) => backend.metadata(item),
+                None => platform::metadata(&self.platform_specific, item, false),
+            }
+        }
+
+        /// Same as [`metadata`](TrashContext::metadata), but also walks the whole item to get
+        /// its [`total_size`](TrashItemMetadata::total_size) and
+        /// [`inode_count`](TrashItemMetadata::inode_count), which may take a while for large directories.
+        ///
+        /// Only the Freedesktop trash and backends that implement
+        /// [`TrashBackend::deep_metadata`](crate::backend::TrashBackend::deep_metadata) support walking items, elsewhere this is the same as `metadata`.
+        ///
+        /// # Example
+        ///
+        /// ```
+        /// use trash::os_limited::{deep_metadata, list};
+        /// for item in list().unwrap().iter().take(3) {
+        ///     println!("{:?}", deep_metadata(item).map(|metadata| metadata.total_size));
+        /// }
+        /// ```
+        pub fn deep_metadata(&self, item: &TrashItem) -> Result<TrashItemMetadata, Error> {
+            match &self.backend {
+                Some(backend) => backend.deep_metadata(item),
+                None => platform::metadata(&self.platform_specific, item, true),
+            }
+        }
+
+        /// Returns the [`TrashItemMetadata`] for each of the provided [`TrashItem`]s, in the order
+        /// the items were provided.
+        ///
+        /// With the "rayon" feature, the metadata of the items is read on multiple threads.
+        ///
+        /// # Example
+        ///
+        /// ```
+        /// use trash::os_limited::{list, metadata_all};
+        /// let trash_items = list().unwrap();
+        /// for (item, metadata) in trash_items.iter().zip(metadata_all(&trash_items)) {
+        ///     println!("{:?}: {:?}", item.name, metadata);
+        /// }
+        /// ```
+        pub fn metadata_all<'a, I>(&self, items: I) -> Vec<Result<TrashItemMetadata, Error>>
+        where
+            I: IntoIterator<Item = &'a TrashItem>,
+        {
+            #[cfg(feature = "rayon")]
+            {
+                use rayon::prelude::*;
+                let items: Vec<&TrashItem> = items.into_iter().collect();
+                items.into_par_iter().map(|item| self.metadata(item)).collect()
+            }
+            #[cfg(not(feature = "rayon"))]
+            {
+                items.into_iter().map(|item| self.metadata(item)).collect()
+            }
+        }
+
+        /// Deletes all the provided [`TrashItem`]s permanently.
+        ///
+        /// This function consumes the provided items.
+        ///
+        /// # Example
+        ///
+        /// Taking items' ownership:
+        ///
+        /// ```
+        /// use std::fs::File;
+        /// use trash::{delete, os_limited::{list, purge_all}};
+        ///
+        /// let filename = "trash-purge_all-example-ownership";
+        /// File::create_new(filename).unwrap();
+        /// delete(filename).unwrap();
+        /// // Collect the filtered list just so that we can make sure there's exactly one element.
+        /// // There's no need to `collect` it otherwise.
+        /// let selected: Vec<_> = list().unwrap().into_iter().filter(|x| x.name == filename).collect();
+        /// assert_eq!(selected.len(), 1);
+        /// purge_all(selected).unwrap();
+        /// ```
+        ///
+        /// Taking items' reference:
+        ///
+        /// ```
+        /// use std::fs::File;
+        /// use trash::{delete, os_limited::{list, purge_all}};
+        ///
+        /// let filename = "trash-purge_all-example-reference";
+        /// File::create_new(filename).unwrap();
+        /// delete(filename).unwrap();
+        /// let mut selected = list().unwrap();
+        /// selected.retain(|x| x.name == filename);
+        /// assert_eq!(selected.len(), 1);
+        /// purge_all(&selected).unwrap();
+        /// ```
+        pub fn purge_all<I>(&self, items: I) -> Result<(), Error>
+        where
+            I: IntoIterator,
+            <I as IntoIterator>::Item: Borrow<TrashItem>,
+        {
+            match &self.backend {
+                Some(backend) => backend.purge_all(items.into_iter().map(|item| item.borrow().clone()).collect()),
+                None => platform::purge_all(&self.platform_specific, items),
+            }
+        }
+
+        /// Restores all the provided [`TrashItem`] to their original location.
+        ///
+        /// This function consumes the provided items.
+        ///
+        /// # Errors
+        ///
+        /// Errors this function may return include but are not limited to the following.
+        ///
+        /// It may be the case that when restoring a file or a folder, the `original_path` already has
+        /// a new item with the same name. When such a collision happens this function returns a
+        /// [`RestoreCollision`] kind of error.
+        ///
+        /// If two or more of the provided items have identical `original_path`s then a
+        /// [`RestoreTwins`] kind of error is returned.
+        ///
+        /// # Example
+        ///
+        /// Basic usage:
+        ///
+        /// ```
+        /// use std::fs::File;
+        /// use trash::os_limited::{list, restore_all};
+        ///
+        /// let filename = "trash-restore_all-example";
+        /// File::create_new(filename).unwrap();
+        /// restore_all(list().unwrap().into_iter().filter(|x| x.name == filename)).unwrap();
+        /// std::fs::remove_file(filename).unwrap();
+        /// ```
+        ///
+        /// Retry restoring when encountering [`RestoreCollision`] error:
+        ///
+        /// ```no_run
+        /// use trash::os_limited::{list, restore_all};
+        /// use trash::Error::RestoreCollision;
+        ///
+        /// let items = list().unwrap();
+        /// if let Err(RestoreCollision { path, mut remaining_items }) = restore_all(items) {
+        ///     // keep all except the one(s) that couldn't be restored
+        ///     remaining_items.retain(|e| e.original_path() != path);
+        ///     restore_all(remaining_items).unwrap();
+        /// }
+        /// ```
+        ///
+        /// [`restore_all_with_options`](TrashContext::restore_all_with_options) can skip, overwrite or
+        /// rename colliding items instead.
+        ///
+        /// [`RestoreCollision`]: Error::RestoreCollision
+        /// [`RestoreTwins`]: Error::RestoreTwins
+        pub fn restore_all<I>(&self, items: I) -> Result<(), Error>
+        where
+            I: IntoIterator<Item = TrashItem>,
+        {
+            let items = check_twins(items.into_iter().collect())?;
+            self.restore_all_unchecked(items)
+        }
+
+        /// Restores all the provided [`TrashItem`]s, resolving conflicts as specified by `options`.
+        ///
+        /// The items are restored one at a time in the order they are provided. When the original
+        /// path of an item is taken, the [`ConflictStrategy`] of `options` decides what happens.
+        /// This also applies to twins, so several versions of one file can be restored in a single
+        /// call with [`ConflictStrategy::Rename`] for example.
+        ///
+        /// With [`ConflictStrategy::Fail`] this behaves exactly like [`restore_all`](TrashContext::restore_all),
+        /// unless [`transactional`](RestoreOptions::transactional) is set.
+        ///
+        /// # Example
+        ///
+        /// ```
+        /// use std::fs::File;
+        /// use trash::{delete, os_limited::list, ConflictStrategy, RestoreOptions, TrashContext};
+        ///
+        /// let filename = "trash-restore_all_with_options-example.txt";
+        /// File::create_new(filename).unwrap();
+        /// delete(filename).unwrap();
+        /// File::create_new(filename).unwrap();
+        ///
+        /// let items = list().unwrap().into_iter().filter(|x| x.name == filename);
+        /// let options = RestoreOptions::new().conflict(ConflictStrategy::Rename);
+        /// TrashContext::default().restore_all_with_options(items, &options).unwrap();
+        /// std::fs::remove_file(filename).unwrap();
+        /// std::fs::remove_file("trash-restore_all_with_options-example (restored).txt").unwrap();
+        /// ```
+        pub fn restore_all_with_options<I>(&self, items: I, options: &RestoreOptions) -> Result<(), Error>
+        where
+            I: IntoIterator<Item = TrashItem>,
+        {
+            if *options == RestoreOptions::new() {
+                return self.restore_all(items);
+            }
+            let outcomes = self.restore_all_with_outcomes(items, options)?;
+            match outcomes.into_iter().find_map(|outcome| outcome.error) {
+                Some(error) => Err(error),
+                None => Ok(()),
+            }
+        }
+
+        /// Same as [`restore_all_with_options`](TrashContext::restore_all_with_options), but returns
+        /// what happened to each item, in the order the items were provided.
+        ///
+        /// With [`continue_on_error`](RestoreOptions::continue_on_error), an item that fails to be
+        /// restored doesn't stop the others from being restored, and its error is part of its outcome
+        /// instead. Otherwise the first error is returned, like `restore_all_with_options` does.
+        ///
+        /// # Example
+        ///
+        /// ```
+        /// use std::fs::File;
+        /// use trash::{delete_with_info, ConflictStrategy, RestoreOptions, TrashContext};
+        ///
+        /// let filename = "trash-restore_all_with_outcomes-example";
+        /// File::create_new(filename).unwrap();
+        /// let item = delete_with_info(filename).unwrap().unwrap();
+        ///
+        /// let options = RestoreOptions::new().continue_on_error(true);
+        /// let outcomes = TrashContext::default().restore_all_with_outcomes([item], &options).unwrap();
+        /// assert!(outcomes[0].error.is_none());
+        /// assert!(!outcomes[0].created_parents);
+        /// std::fs::remove_file(outcomes[0].path.as_ref().unwrap()).unwrap();
+        /// ```
+        pub fn restore_all_with_outcomes<I>(
+            &self,
+            items: I,
+            options: &RestoreOptions,
+        ) -> Result<Vec<RestoreOutcome>, Error>
+        where
+            I: IntoIterator<Item = TrashItem>,
+        {
+            // Fail before anything is restored or overwritten
+            #[cfg(target_os = "windows")]
+            if self.backend.is_none() {
+                if options.transactional {
+                    return Err(platform::unsupported_restore_option("transactional"));
+                }
+                if options.merge_directories.is_some() {
+                    return Err(platform::unsupported_restore_option("merge_directories"));
+                }
+            }
+            let items: Vec<TrashItem> = items.into_iter().collect();
+            if options.transactional {
+                return self.restore_all_transactional(items, options.conflict);
+            }
+            let items = if options.conflict == ConflictStrategy::Fail && !options.continue_on_error {
+                check_twins(items)?
+            } else {
+                items
+            };
+            let mut outcomes = Vec::with_capacity(items.len());
+            let mut iter = items.into_iter();
+            while let Some(item) = iter.next() {
+                let created_parents = !item.original_parent.exists();
+                match self.restore_resolving_conflict(&item, options) {
+                    Ok(path) => {
+                        let created_parents = created_parents && path.is_some();
+                        outcomes.push(RestoreOutcome { item, path, created_parents, error: None });
+                    }
+                    Err(error) if options.continue_on_error => {
+                        outcomes.push(RestoreOutcome { item, path: None, created_parents: false, error: Some(error) });
+                    }
+                    Err(Error::RestoreCollision { path, mut remaining_items }) => {
+                        remaining_items.extend(iter);
+                        return Err(Error::RestoreCollision { path, remaining_items });
+                    }
+                    Err(error) => return Err(error),
+                }
+            }
+            Ok(outcomes)
+        }
+
+        /// Checks what would happen when restoring the provided [`TrashItem`]s, without restoring
+        /// anything.
+        ///
+        /// Unlike the errors of [`restore_all`](TrashContext::restore_all), which only report the
+        /// first problem, the plan reports every collision, twin and inaccessible parent at once.
+        ///
+        /// # Example
+        ///
+        /// ```
+        /// use std::fs::File;
+        /// use trash::{delete_with_info, os_limited::{plan_restore, purge_all}, ExistingKind, RestoreStatus};
+        ///
+        /// let filename = "trash-plan_restore-example";
+        /// File::create_new(filename).unwrap();
+        /// let item = delete_with_info(filename).unwrap().unwrap();
+        /// File::create_new(filename).unwrap();
+        ///
+        /// let plan = plan_restore([item.clone()]);
+        /// assert_eq!(plan.items[0].status, RestoreStatus::Collision(ExistingKind::File));
+        /// std::fs::remove_file(filename).unwrap();
+        /// assert!(plan_restore([item.clone()]).is_ok());
+        /// purge_all([item]).unwrap();
+        /// ```
+        pub fn plan_restore<I>(&self, items: I) -> RestorePlan
+        where
+            I: IntoIterator<Item = TrashItem>,
+        {
+            let mut planned: Vec<PlannedRestore> = Vec::new();
+            for item in items {
+                let path = item.original_path();
+                let status = match self.restore_status(&item) {
+                    RestoreStatus::Collision(kind) => RestoreStatus::Collision(kind),
+                    _ if planned.iter().any(|earlier| earlier.item.original_path() == path) => RestoreStatus::Twin,
+                    status => status,
+                };
+                planned.push(PlannedRestore { item, status });
+            }
+            RestorePlan { items: planned }
+        }
+
+        /// Restores a [`TrashItem`] into `destination_dir` instead of its original parent, returning the
+        /// path of the restored item.
+        ///
+        /// The item keeps its name unless `new_name` is provided. Unlike [`restore_all`](TrashContext::restore_all),
+        /// the destination is not created, it must be an existing directory.
+        ///
+        /// # Errors
+        ///
+        /// If the destination already contains an item with the same name, then a
+        /// [`RestoreCollision`](Error::RestoreCollision) kind of error is returned and the item stays in
+        /// the trash. If `destination_dir` is not a directory, [`CouldNotAccess`](Error::CouldNotAccess)
+        /// is returned.
+        ///
+        /// # Example
+        ///
+        /// ```
+        /// use std::{ffi::OsStr, fs::File};
+        /// use trash::{delete, os_limited::{list, restore_to}};
+        ///
+        /// let filename = "trash-restore_to-example";
+        /// File::create_new(filename).unwrap();
+        /// delete(filename).unwrap();
+        /// let item = list().unwrap().into_iter().find(|x| x.name == filename).unwrap();
+        /// std::fs::create_dir("trash-restore_to-example-dir").unwrap();
+        /// let restored = restore_to(item, "trash-restore_to-example-dir", Some(OsStr::new("restored"))).unwrap();
+        /// assert!(restored.ends_with("trash-restore_to-example-dir/restored"));
+        /// std::fs::remove_dir_all("trash-restore_to-example-dir").unwrap();
+        /// ```
+        pub fn restore_to(
+            &self,
+            item: TrashItem,
+            destination_dir: impl AsRef<Path>,
+            new_name: Option<&OsStr>,
+        ) -> Result<PathBuf, Error> {
+            let destination_dir = destination_dir.as_ref();
+            let destination_dir = if destination_dir.is_relative() {
+                current_dir()
+                    .map_err(|_| Error::CouldNotAccess { target: "[Current working directory]".into() })?
+                    .join(destination_dir)
+            } else {
+                destination_dir.to_owned()
+            };
+            if !destination_dir.is_dir() {
+                return Err(Error::CouldNotAccess { target: destination_dir.to_string_lossy().into_owned() });
+            }
+            let name = new_name.map(OsStr::to_owned).unwrap_or_else(|| item.name.clone());
+            if Path::new(&name).file_name() != Some(&name) {
+                return Err(Error::Unknown { description: format!("{name:?} is not a valid file name") });
+            }
+            let target = TrashItem { name, original_parent: destination_dir, ..item };
+            let path = target.original_path();
+            self.restore_all_unchecked(vec![target])?;
+            Ok(path)
+        }
+
+        /// Restores a single item, trying again according to `options` as long as its target is taken.
+        ///
+        /// Returns the path the item was restored to, or `None` if it was skipped.
+        fn restore_resolving_conflict(
+            &self,
+            item: &TrashItem,
+            options: &RestoreOptions,
+        ) -> Result<Option<PathBuf>, Error> {
+            let mut target = item.clone();
+            let mut overwritten = false;
+            let mut attempt = 0;
+            loop {
+                match self.restore_all_unchecked(vec![target.clone()]) {
+                    Err(Error::RestoreCollision { path, .. }) => {
+                        // Merging only applies to the original path, not to renamed targets
+                        if let (Some(entry_conflict), None, 0) = (options.merge_directories, &self.backend, attempt) {
+                            if self.restore_merging(item, entry_conflict)? {
+                                return Ok(Some(item.original_path()));
+                            }
+                        }
+                        match options.conflict {
+                            ConflictStrategy::Skip => return Ok(None),
+                            // Only overwrite once, in case the path is taken again right away.
+                            ConflictStrategy::Overwrite if !overwritten => {
+                                self.delete(&path)?;
+                                overwritten = true;
+                            }
+                            ConflictStrategy::Rename => {
+                                attempt += 1;
+                                target.name = restored_name(&item.name, attempt);
+                            }
+                            _ => return Err(Error::RestoreCollision { path, remaining_items: vec![item.clone()] }),
+                        }
+                    }
+                    result => return result.map(|_| Some(target.original_path())),
+                }
+            }
+        }
+
+        fn restore_all_transactional(
+            &self,
+            items: Vec<TrashItem>,
+            conflict: ConflictStrategy,
+        ) -> Result<Vec<RestoreOutcome>, Error> {
+            let (targets, taken_paths) = plan_targets(self, &items, conflict)?;
+            let outcomes: Vec<_> = items
+                .into_iter()
+                .zip(&targets)
+                .map(|(item, target)| RestoreOutcome {
+                    created_parents: target.is_some() && !item.original_parent.exists(),
+                    path: target.as_ref().map(TrashItem::original_path),
+                    item,
+                    error: None,
+                })
+                .collect();
+            let targets: Vec<_> = targets.into_iter().flatten().collect();
+            // Only overwrite once every item is checked, so that nothing is touched when a check fails.
+            if self.backend.is_none() {
+                platform::check_restore_transactional(&targets, &taken_paths)?;
+            }
+            // The taken paths are overwritten one by one, so that the ones that were moved to the
+            // trash are known when a later one fails.
+            let mut overwritten = Vec::with_capacity(taken_paths.len());
+            let mut result = Ok(());
+            for path in &taken_paths {
+                match self.delete_all_with_info([path]) {
+                    Ok(items) => overwritten.extend(items.into_iter().flatten()),
+                    Err(error) => {
+                        result = Err(error);
+                        break;
+                    }
+                }
+            }
+            if result.is_ok() {
+                result = match &self.backend {
+                    Some(backend) => backend.restore_all(targets),
+                    None => platform::restore_all_transactional(&self.platform_specific, targets),
+                };
+            }
+            if result.is_err() && !overwritten.is_empty() {
+                if let Err(e) = self.restore_all_unchecked(overwritten) {
+                    warn!("Failed to restore the items that were overwritten. The error was: {:?}", e);
+                }
+            }
+            result.map(|_| outcomes)
+        }
+
+        /// What restoring `item` to its original path runs into, not considering other items.
+        fn restore_status(&self, item: &TrashItem) -> RestoreStatus {
+            if let Some(status) = self.backend.as_ref().and_then(|backend| backend.restore_status(item)) {
+                return status;
+            }
+            match item.original_path().symlink_metadata() {
+                Ok(metadata) => RestoreStatus::Collision(metadata.file_type().into()),
+                Err(_) => parent_status(&item.original_parent),
+            }
+        }
+
+        fn restore_all_unchecked(&self, items: Vec<TrashItem>) -> Result<(), Error> {
+            match &self.backend {
+                Some(backend) => backend.restore_all(items),
+                None => platform::restore_all(&self.platform_specific, items),
+            }
+        }
+    }
+
+    /// Checks whether items can be created in `parent`, or whether it can be created.
+    fn parent_status(parent: &Path) -> RestoreStatus {
+        let Some(existing) = parent.ancestors().find(|ancestor| ancestor.symlink_metadata().is_ok()) else {
+            return RestoreStatus::NeedsParent;
+        };
+        if !existing.is_dir() {
+            RestoreStatus::ParentNotDirectory(existing.to_owned())
+        } else if !platform::is_writable_dir(existing) {
+            RestoreStatus::PermissionDenied(existing.to_owned())
+        } else if existing == parent {
+            RestoreStatus::Ready
+        } else {
+            RestoreStatus::NeedsParent
+        }
+    }
+
+    /// Determines where each item is restored to, applying `conflict` to the paths that are taken.
     ///
-    /// # Example
+    /// Returns for each item a copy with the name it's restored under, or `None` if it stays in the
+    /// trash, and the taken paths that must be moved to the trash first.
+    fn plan_targets(
+        ctx: &TrashContext,
+        items: &[TrashItem],
+        conflict: ConflictStrategy,
+    ) -> Result<(Vec<Option<TrashItem>>, Vec<PathBuf>), Error> {
+        let mut targets: Vec<Option<TrashItem>> = Vec::with_capacity(items.len());
+        let mut taken_paths = Vec::new();
+        for item in items {
+            let mut target = Some(item.clone());
+            let mut attempt = 0;
+            while let Some(planned) = &mut target {
+                let path = planned.original_path();
+                let twin =
+                    targets.iter().position(|earlier| earlier.as_ref().is_some_and(|e| e.original_path() == path));
+                let exists =
+                    matches!(ctx.restore_status(planned), RestoreStatus::Collision(_)) && !taken_paths.contains(&path);
+                if twin.is_none() && !exists {
+                    break;
+                }
+                match conflict {
+                    ConflictStrategy::Fail if twin.is_some() => {
+                        return Err(Error::RestoreTwins { path, items: items.to_vec() })
+                    }
+                    ConflictStrategy::Fail => {
+                        return Err(Error::RestoreCollision { path, remaining_items: items.to_vec() })
+                    }
+                    ConflictStrategy::Skip => target = None,
+                    ConflictStrategy::Overwrite => {
+                        // The later twin wins, the earlier one stays in the trash.
+                        if let Some(twin) = twin {
+                            targets[twin] = None;
+                        } else {
+                            taken_paths.push(path);
+                        }
+                        break;
+                    }
+                    ConflictStrategy::Rename => {
+                        attempt += 1;
+                        planned.name = restored_name(&item.name, attempt);
+                    }
+                }
+            }
+            targets.push(target);
+        }
+        Ok((targets, taken_paths))
+    }
+
+    /// Returns `name (restored).ext` for the first attempt and `name (restored N).ext` for later ones.
+    pub(crate) fn restored_name(name: &OsStr, attempt: usize) -> OsString {
+        let path = Path::new(name);
+        let mut restored = path.file_stem().unwrap_or(name).to_owned();
+        if attempt == 1 {
+            restored.push(" (restored)");
+        } else {
+            restored.push(format!(" (restored {attempt})"));
+        }
+        if let Some(extension) = path.extension() {
+            restored.push(".");
+            restored.push(extension);
+        }
+        restored
+    }
+
+    /// Returns the items unchanged, unless multiple items share the same `original_path`.
+    pub(crate) fn check_twins(items: Vec<TrashItem>) -> Result<Vec<TrashItem>, Error> {
+        // Check for twins here cause that's pretty platform independent.
+        struct ItemWrapper<'a>(&'a TrashItem);
+        impl PartialEq for ItemWrapper<'_> {
+            fn eq(&self, other: &Self) -> bool {
+                self.0.original_path() == other.0.original_path()
+            }
+        }
+        impl Eq for ItemWrapper<'_> {}
+        impl Hash for ItemWrapper<'_> {
+            fn hash<H: Hasher>(&self, state: &mut H) {
+                self.0.original_path().hash(state);
+            }
+        }
+        let mut item_set = HashSet::with_capacity(items.len());
+        for item in items.iter() {
+            if !item_set.insert(ItemWrapper(item)) {
+                return Err(Error::RestoreTwins { path: item.original_path(), items });
+            }
+        }
+        Ok(items)
+    }
+
+    /// Convenience method for `DEFAULT_TRASH_CTX.list()`.
     ///
-    /// ```
-    /// use trash::os_limited::list;
-    /// let trash_items = list().unwrap();
-    /// println!("{:#?}", trash_items);
-    /// ```
+    /// See: [`TrashContext::list`](TrashContext::list)
     pub fn list() -> Result<Vec<TrashItem>, Error> {
-        platform::list()
+        DEFAULT_TRASH_CTX.list()
     }
 
-    /// Returns whether the trash is empty or has at least one item.
+    /// Convenience method for `DEFAULT_TRASH_CTX.list_sorted()`.
     ///
-    /// Unlike calling [`list`], this function short circuits without evaluating every item.
+    /// See: [`TrashContext::list_sorted`](TrashContext::list_sorted)
+    pub fn list_sorted() -> Result<Vec<TrashItem>, Error> {
+        DEFAULT_TRASH_CTX.list_sorted()
+    }
+
+    /// Convenience method for `DEFAULT_TRASH_CTX.check_trash()`.
     ///
-    /// # Example
+    /// See: [`TrashContext::check_trash`](TrashContext::check_trash)
+    #[cfg(all(unix, not(target_os = "macos"), not(target_os = "ios"), not(target_os = "android")))]
+    pub fn check_trash() -> Result<Vec<crate::freedesktop::TrashProblem>, Error> {
+        DEFAULT_TRASH_CTX.check_trash()
+    }
+
+    /// Convenience method for `DEFAULT_TRASH_CTX.repair()`.
     ///
-    /// ```
-    /// use trash::os_limited::is_empty;
-    /// if is_empty().unwrap_or(true) {
-    ///     println!("Trash is empty");
-    /// } else {
-    ///     println!("Trash contains at least one item");
-    /// }
-    /// ```
-    pub fn is_empty() -> Result<bool, Error> {
-        platform::is_empty()
+    /// See: [`TrashContext::repair`](TrashContext::repair)
+    #[cfg(all(unix, not(target_os = "macos"), not(target_os = "ios"), not(target_os = "android")))]
+    pub fn repair(
+        options: &crate::freedesktop::RepairOptions,
+    ) -> Result<Vec<crate::freedesktop::RepairOutcome>, Error> {
+        DEFAULT_TRASH_CTX.repair(options)
     }
 
-    /// Returns all valid trash bins on supported Unix platforms.
+    /// Convenience method for `DEFAULT_TRASH_CTX.list_with_diagnostics()`.
     ///
-    /// Valid trash folders include the user's personal "home trash" as well as designated trash
-    /// bins across mount points. Some, or all of these, may not exist or be invalid in some way.
+    /// See: [`TrashContext::list_with_diagnostics`](TrashContext::list_with_diagnostics)
+    pub fn list_with_diagnostics() -> Result<(Vec<TrashItem>, Vec<ListIssue>), Error> {
+        DEFAULT_TRASH_CTX.list_with_diagnostics()
+    }
+
+    /// Convenience method for `DEFAULT_TRASH_CTX.list_iter()`.
     ///
-    /// # Example
+    /// See: [`TrashContext::list_iter`](TrashContext::list_iter)
+    pub fn list_iter() -> Result<ListIter, Error> {
+        DEFAULT_TRASH_CTX.list_iter()
+    }
+
+    /// Convenience method for `DEFAULT_TRASH_CTX.list_iter_from()`.
     ///
-    /// ```
-    /// # #[cfg(all(unix, not(target_os = "macos"), not(target_os = "ios"), not(target_os = "android")))] {
-    /// use trash::os_limited::trash_folders;
-    /// let trash_bins = trash_folders()?;
-    /// println!("{trash_bins:#?}");
-    /// # }
-    /// # Ok::<(), trash::Error>(())
-    /// ```
+    /// See: [`TrashContext::list_iter_from`](TrashContext::list_iter_from)
+    pub fn list_iter_from(offset: usize) -> Result<ListIter, Error> {
+        DEFAULT_TRASH_CTX.list_iter_from(offset)
+    }
+
+    /// Convenience method for `DEFAULT_TRASH_CTX.is_empty()`.
+    ///
+    /// See: [`TrashContext::is_empty`](TrashContext::is_empty)
+    pub fn is_empty() -> Result<bool, Error> {
+        DEFAULT_TRASH_CTX.is_empty()
+    }
+
+    /// Convenience method for `DEFAULT_TRASH_CTX.trash_folders()`.
+    ///
+    /// See: [`TrashContext::trash_folders`](TrashContext::trash_folders)
     #[cfg(all(unix, not(target_os = "macos"), not(target_os = "ios"), not(target_os = "android")))]
     pub fn trash_folders() -> Result<HashSet<std::path::PathBuf>, Error> {
-        platform::trash_folders()
+        DEFAULT_TRASH_CTX.trash_folders()
     }
 
-    /// Returns the [`TrashItemMetadata`] for a [`TrashItem`]
-    ///
-    /// # Example
+    /// Convenience method for `DEFAULT_TRASH_CTX.metadata()`.
     ///
-    /// ```
-    /// use trash::os_limited::{list, metadata};
-    /// let trash_items = list().unwrap();
-    /// for item in trash_items {
-    ///     println!("{:#?}", metadata(&item).unwrap());
-    /// }
-    /// ```
+    /// See: [`TrashContext::metadata`](TrashContext::metadata)
     pub fn metadata(item: &TrashItem) -> Result<TrashItemMetadata, Error> {
-        platform::metadata(item)
+        DEFAULT_TRASH_CTX.metadata(item)
     }
 
-    /// Deletes all the provided [`TrashItem`]s permanently.
+    /// Convenience method for `DEFAULT_TRASH_CTX.deep_metadata()`.
     ///
-    /// This function consumes the provided items.
-    ///
-    /// # Example
-    ///
-    /// Taking items' ownership:
-    ///
-    /// ```
-    /// use std::fs::File;
-    /// use trash::{delete, os_limited::{list, purge_all}};
-    ///
-    /// let filename = "trash-purge_all-example-ownership";
-    /// File::create_new(filename).unwrap();
-    /// delete(filename).unwrap();
-    /// // Collect the filtered list just so that we can make sure there's exactly one element.
-    /// // There's no need to `collect` it otherwise.
-    /// let selected: Vec<_> = list().unwrap().into_iter().filter(|x| x.name == filename).collect();
-    /// assert_eq!(selected.len(), 1);
-    /// purge_all(selected).unwrap();
-    /// ```
+    /// See: [`TrashContext::deep_metadata`](TrashContext::deep_metadata)
+    pub fn deep_metadata(item: &TrashItem) -> Result<TrashItemMetadata, Error> {
+        DEFAULT_TRASH_CTX.deep_metadata(item)
+    }
+
+    /// Convenience method for `DEFAULT_TRASH_CTX.metadata_all()`.
     ///
-    /// Taking items' reference:
+    /// See: [`TrashContext::metadata_all`](TrashContext::metadata_all)
+    pub fn metadata_all<'a, I>(items: I) -> Vec<Result<TrashItemMetadata, Error>>
+    where
+        I: IntoIterator<Item = &'a TrashItem>,
+    {
+        DEFAULT_TRASH_CTX.metadata_all(items)
+    }
+
+    /// Convenience method for `DEFAULT_TRASH_CTX.purge_all()`.
     ///
-    /// ```
-    /// use std::fs::File;
-    /// use trash::{delete, os_limited::{list, purge_all}};
-    ///
-    /// let filename = "trash-purge_all-example-reference";
-    /// File::create_new(filename).unwrap();
-    /// delete(filename).unwrap();
-    /// let mut selected = list().unwrap();
-    /// selected.retain(|x| x.name == filename);
-    /// assert_eq!(selected.len(), 1);
-    /// purge_all(&selected).unwrap();
-    /// ```
+    /// See: [`TrashContext::purge_all`](TrashContext::purge_all)
     pub fn purge_all<I>(items: I) -> Result<(), Error>
     where
         I: IntoIterator,
         <I as IntoIterator>::Item: Borrow<TrashItem>,
     {
-        platform::purge_all(items)
+        DEFAULT_TRASH_CTX.purge_all(items)
     }
 
-    /// Restores all the provided [`TrashItem`] to their original location.
+    /// Convenience method for `DEFAULT_TRASH_CTX.restore_all()`.
     ///
-    /// This function consumes the provided items.
-    ///
-    /// # Errors
-    ///
-    /// Errors this function may return include but are not limited to the following.
-    ///
-    /// It may be the case that when restoring a file or a folder, the `original_path` already has
-    /// a new item with the same name. When such a collision happens this function returns a
-    /// [`RestoreCollision`] kind of error.
-    ///
-    /// If two or more of the provided items have identical `original_path`s then a
-    /// [`RestoreTwins`] kind of error is returned.
-    ///
-    /// # Example
-    ///
-    /// Basic usage:
-    ///
-    /// ```
-    /// use std::fs::File;
-    /// use trash::os_limited::{list, restore_all};
-    ///
-    /// let filename = "trash-restore_all-example";
-    /// File::create_new(filename).unwrap();
-    /// restore_all(list().unwrap().into_iter().filter(|x| x.name == filename)).unwrap();
-    /// std::fs::remove_file(filename).unwrap();
-    /// ```
+    /// See: [`TrashContext::restore_all`](TrashContext::restore_all)
+    pub fn restore_all<I>(items: I) -> Result<(), Error>
+    where
+        I: IntoIterator<Item = TrashItem>,
+    {
+        DEFAULT_TRASH_CTX.restore_all(items)
+    }
+
+    /// Convenience method for `DEFAULT_TRASH_CTX.plan_restore()`.
     ///
-    /// Retry restoring when encountering [`RestoreCollision`] error:
+    /// See: [`TrashContext::plan_restore`](TrashContext::plan_restore)
+    pub fn plan_restore<I>(items: I) -> RestorePlan
+    where
+        I: IntoIterator<Item = TrashItem>,
+    {
+        DEFAULT_TRASH_CTX.plan_restore(items)
+    }
+
+    /// Convenience method for `DEFAULT_TRASH_CTX.restore_to()`.
     ///
-    /// ```no_run
-    /// use trash::os_limited::{list, restore_all};
-    /// use trash::Error::RestoreCollision;
+    /// See: [`TrashContext::restore_to`](TrashContext::restore_to)
+    pub fn restore_to(
+        item: TrashItem,
+        destination_dir: impl AsRef<Path>,
+        new_name: Option<&OsStr>,
+    ) -> Result<PathBuf, Error> {
+        DEFAULT_TRASH_CTX.restore_to(item, destination_dir, new_name)
+    }
+
+    /// Convenience method for `DEFAULT_TRASH_CTX.restore_all_with_options()`.
     ///
-    /// let items = list().unwrap();
-    /// if let Err(RestoreCollision { path, mut remaining_items }) = restore_all(items) {
-    ///     // keep all except the one(s) that couldn't be restored
-    ///     remaining_items.retain(|e| e.original_path() != path);
-    ///     restore_all(remaining_items).unwrap();
-    /// }
-    /// ```
+    /// See: [`TrashContext::restore_all_with_options`](TrashContext::restore_all_with_options)
+    pub fn restore_all_with_options<I>(items: I, options: &RestoreOptions) -> Result<(), Error>
+    where
+        I: IntoIterator<Item = TrashItem>,
+    {
+        DEFAULT_TRASH_CTX.restore_all_with_options(items, options)
+    }
+
+    /// Convenience method for `DEFAULT_TRASH_CTX.restore_all_with_outcomes()`.
     ///
-    /// [`RestoreCollision`]: Error::RestoreCollision
-    /// [`RestoreTwins`]: Error::RestoreTwins
-    pub fn restore_all<I>(items: I) -> Result<(), Error>
+    /// See: [`TrashContext::restore_all_with_outcomes`](TrashContext::restore_all_with_outcomes)
+    pub fn restore_all_with_outcomes<I>(items: I, options: &RestoreOptions) -> Result<Vec<RestoreOutcome>, Error>
     where
         I: IntoIterator<Item = TrashItem>,
     {
-        // Check for twins here cause that's pretty platform independent.
-        struct ItemWrapper<'a>(&'a TrashItem);
-        impl PartialEq for ItemWrapper<'_> {
-            fn eq(&self, other: &Self) -> bool {
-                self.0.original_path() == other.0.original_path()
-            }
-        }
-        impl Eq for ItemWrapper<'_> {}
-        impl Hash for ItemWrapper<'_> {
-            fn hash<H: Hasher>(&self, state: &mut H) {
-                self.0.original_path().hash(state);
-            }
-        }
-        let items = items.into_iter().collect::<Vec<_>>();
-        let mut item_set = HashSet::with_capacity(items.len());
-        for item in items.iter() {
-            if !item_set.insert(ItemWrapper(item)) {
-                return Err(Error::RestoreTwins { path: item.original_path(), items });
-            }
-        }
-        platform::restore_all(items)
+        DEFAULT_TRASH_CTX.restore_all_with_outcomes(items, options)
     }
 }
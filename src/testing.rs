@@ -0,0 +1,125 @@
+//! An isolated environment for testing code that uses the Freedesktop trash.
+//!
+//! A [`TrashWorld`] lives in a temporary directory that is removed when the world is dropped. It has
+//! its own home and a synthetic mount table, and hands out [`TrashContext`]s that find every trash
+//! folder inside of it. Neither the trash of the user nor the mount table of the system is used, so
+//! topdir trashes can be tested without root privileges or real mounts.
+//!
+//! Note that every mount point of a world resides on the same real file system.
+//!
+//! # Example
+//!
+//! ```
+//! use std::fs::File;
+//! use trash::testing::TrashWorld;
+//!
+//! let mut world = TrashWorld::new().unwrap();
+//! let usb = world.add_mount("usb").unwrap();
+//! File::create_new(usb.join("photo.jpg")).unwrap();
+//! world.context().delete(usb.join("photo.jpg")).unwrap();
+//! assert!(world.user_trash(&usb).join("files/photo.jpg").is_file());
+//! ```
+
+use std::{
+    fs, io,
+    os::unix::fs::PermissionsExt,
+    path::{Path, PathBuf},
+};
+
+use tempfile::TempDir;
+
+use crate::{
+    freedesktop::{MountPoint, TrashContextExtFreedesktop},
+    TrashContext,
+};
+
+/// A temporary directory with a home trash and mount points of its own.
+///
+/// The root of the world is the first mount point and contains the home directory, so items
+/// anywhere in the world except other mount points are moved to the home trash.
+#[derive(Debug)]
+pub struct TrashWorld {
+    // Removes the world once dropped.
+    _dir: TempDir,
+    root: PathBuf,
+    mount_points: Vec<MountPoint>,
+}
+
+impl TrashWorld {
+    /// Creates a world in a new temporary directory.
+    pub fn new() -> io::Result<Self> {
+        let dir = TempDir::new()?;
+        // Canonicalize since the paths of deleted items are canonicalized as well.
+        let root = dir.path().canonicalize()?;
+        fs::create_dir(root.join("home"))?;
+        let mount_points = vec![mount_point(&root)];
+        Ok(Self { _dir: dir, root, mount_points })
+    }
+
+    /// The directory that contains the whole world.
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+
+    /// The home directory of the world.
+    pub fn home(&self) -> PathBuf {
+        self.root().join("home")
+    }
+
+    /// The home trash of the world, which is created on demand like `$XDG_DATA_HOME/Trash`.
+    pub fn home_trash(&self) -> PathBuf {
+        self.home().join(".local/share/Trash")
+    }
+
+    /// Creates the directory `name` relative to the root and adds it to the mount table.
+    ///
+    /// Returns the path of the new mount point, its topdir.
+    pub fn add_mount(&mut self, name: impl AsRef<Path>) -> io::Result<PathBuf> {
+        let topdir = self.root().join(name);
+        fs::create_dir_all(&topdir)?;
+        self.mount_points.push(mount_point(&topdir));
+        Ok(topdir)
+    }
+
+    /// The synthetic mount table of the world.
+    pub fn mount_points(&self) -> &[MountPoint] {
+        &self.mount_points
+    }
+
+    /// Creates `$topdir/.Trash`, the way an administrator would, and returns its path.
+    ///
+    /// The specification requires the sticky bit to be set, otherwise the folder is ignored.
+    pub fn create_admin_trash(&self, topdir: impl AsRef<Path>, sticky: bool) -> io::Result<PathBuf> {
+        let path = topdir.as_ref().join(".Trash");
+        fs::create_dir(&path)?;
+        let mode = if sticky { 0o1777 } else { 0o777 };
+        fs::set_permissions(&path, fs::Permissions::from_mode(mode))?;
+        Ok(path)
+    }
+
+    /// The trash folder of the current user inside `$topdir/.Trash`, that is `$topdir/.Trash/$uid`.
+    pub fn admin_trash(&self, topdir: impl AsRef<Path>) -> PathBuf {
+        topdir.as_ref().join(".Trash").join(uid().to_string())
+    }
+
+    /// The trash folder of the current user at the root of `topdir`, that is `$topdir/.Trash-$uid`.
+    pub fn user_trash(&self, topdir: impl AsRef<Path>) -> PathBuf {
+        topdir.as_ref().join(format!(".Trash-{}", uid()))
+    }
+
+    /// Returns a context that uses the home trash and the mount table of this world.
+    pub fn context(&self) -> TrashContext {
+        let mut ctx = TrashContext::new();
+        ctx.set_home_trash(Some(self.home_trash()));
+        ctx.set_mount_points(Some(self.mount_points.clone()));
+        ctx
+    }
+}
+
+fn mount_point(topdir: &Path) -> MountPoint {
+    MountPoint { mnt_dir: topdir.to_owned(), mnt_type: "testing".into(), mnt_fsname: "testing".into() }
+}
+
+fn uid() -> u32 {
+    unsafe { libc::getuid() }
+}
@@ -44,8 +44,8 @@ mod os_limited {
         init_logging();
 
         let deletion_time = chrono::Utc::now();
-        let actual_unix_deletion_time = deletion_time.naive_utc().timestamp();
-        assert_eq!(actual_unix_deletion_time, deletion_time.naive_local().timestamp());
+        let actual_unix_deletion_time = deletion_time.naive_utc().and_utc().timestamp();
+        assert_eq!(actual_unix_deletion_time, deletion_time.naive_local().and_utc().timestamp());
         let file_name_prefix = get_unique_name();
         let batches: usize = 2;
         let files_per_batch: usize = 3;
@@ -94,7 +94,7 @@ mod os_limited {
 
         // Let's try to purge all the items we just created but ignore any errors
         // as this test should succeed as long as `list` works properly.
-        let _ = trash::os_limited::purge_all(items.iter().flat_map(|(_name, item)| item));
+        let _ = trash::os_limited::purge_all(items.values().flatten());
     }
 
     #[cfg(all(unix, not(target_os = "macos"), not(target_os = "ios"), not(target_os = "android")))]
@@ -288,6 +288,65 @@ mod os_limited {
         }
     }
 
+    #[test]
+    #[serial]
+    fn restore_with_conflict_strategies() {
+        use trash::{ConflictStrategy, RestoreOptions, TrashContext};
+        init_logging();
+        let ctx = TrashContext::default();
+        let prefix = get_unique_name();
+        let name = format!("{prefix}.txt");
+        let list_prefixed = || {
+            let mut items: Vec<_> = trash::os_limited::list()
+                .unwrap()
+                .into_iter()
+                .filter(|x| x.name.as_encoded_bytes().starts_with(prefix.as_bytes()))
+                .collect();
+            items.sort_by(|a, b| a.id.cmp(&b.id));
+            items
+        };
+        // Three versions of the same file, with the newest one at the original path
+        for content in ["first", "second", "third", "current"] {
+            std::fs::write(&name, content).unwrap();
+            if content != "current" {
+                trash::delete(&name).unwrap();
+            }
+        }
+        assert_eq!(list_prefixed().len(), 3);
+
+        let skip = RestoreOptions::new().conflict(ConflictStrategy::Skip);
+        ctx.restore_all_with_options(list_prefixed(), &skip).unwrap();
+        assert_eq!(list_prefixed().len(), 3);
+        assert_eq!(std::fs::read_to_string(&name).unwrap(), "current");
+
+        let fail = RestoreOptions::new();
+        assert!(matches!(ctx.restore_all_with_options(list_prefixed(), &fail), Err(trash::Error::RestoreTwins { .. })));
+
+        let rename = RestoreOptions::new().conflict(ConflictStrategy::Rename);
+        ctx.restore_all_with_options(list_prefixed().into_iter().take(2), &rename).unwrap();
+        let restored = format!("{prefix} (restored).txt");
+        let restored_2 = format!("{prefix} (restored 2).txt");
+        let mut versions =
+            vec![std::fs::read_to_string(&restored).unwrap(), std::fs::read_to_string(&restored_2).unwrap()];
+
+        let overwrite = RestoreOptions::new().conflict(ConflictStrategy::Overwrite);
+        let remaining = list_prefixed();
+        assert_eq!(remaining.len(), 1);
+        ctx.restore_all_with_options(remaining, &overwrite).unwrap();
+        versions.push(std::fs::read_to_string(&name).unwrap());
+        versions.sort();
+        assert_eq!(versions, ["first", "second", "third"]);
+        // The file at the original path was moved to the trash instead
+        let overwritten = list_prefixed();
+        assert_eq!(overwritten.len(), 1);
+        assert_eq!(overwritten[0].name, OsStr::new(&name));
+
+        trash::os_limited::purge_all(overwritten).unwrap();
+        for path in [&name, &restored, &restored_2] {
+            std::fs::remove_file(path).unwrap();
+        }
+    }
+
     #[test]
     #[serial]
     fn is_empty_matches_list() {
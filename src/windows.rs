@@ -1,4 +1,7 @@
-use crate::{Error, TrashContext, TrashItem, TrashItemMetadata, TrashItemSize};
+use crate::{
+    ConflictStrategy, Error, ExistingKind, ListError, ListIssue, TrashContext, TrashItem, TrashItemMetadata,
+    TrashItemSize,
+};
 use std::{
     borrow::Borrow,
     ffi::{c_void, OsStr, OsString},
@@ -73,6 +76,12 @@ impl TrashContext {
         }
     }
 
+    /// The recycle bin doesn't give access to the entries of a deleted folder, so folders can't
+    /// be merged.
+    pub(crate) fn restore_merging(&self, _item: &TrashItem, _entry_conflict: ConflictStrategy) -> Result<bool, Error> {
+        Err(unsupported_restore_option("merge_directories"))
+    }
+
     /// Removes all files and folder paths recursively.
     pub(crate) fn delete_all_canonicalized(
         &self,
@@ -83,7 +92,7 @@ impl TrashContext {
     }
 }
 
-pub fn list() -> Result<Vec<TrashItem>, Error> {
+pub fn list(_ctx: &PlatformTrashContext) -> Result<Vec<TrashItem>, Error> {
     ensure_com_initialized();
     unsafe {
         let mut item_vec = Vec::new();
@@ -132,7 +141,18 @@ pub fn list() -> Result<Vec<TrashItem>, Error> {
     }
 }
 
-pub fn is_empty() -> Result<bool, Error> {
+pub fn list_with_diagnostics(ctx: &PlatformTrashContext) -> Result<(Vec<TrashItem>, Vec<ListIssue>), Error> {
+    Ok((list(ctx)?, vec![]))
+}
+
+pub fn list_iter(
+    ctx: &PlatformTrashContext,
+    offset: usize,
+) -> Result<impl Iterator<Item = (usize, Result<TrashItem, ListError>)> + Send, Error> {
+    Ok(list(ctx)?.into_iter().map(Ok).enumerate().skip(offset).map(|(i, result)| (i + 1, result)))
+}
+
+pub fn is_empty(_ctx: &PlatformTrashContext) -> Result<bool, Error> {
     ensure_com_initialized();
     unsafe {
         let recycle_bin: IShellItem =
@@ -147,7 +167,7 @@ pub fn is_empty() -> Result<bool, Error> {
     }
 }
 
-pub fn metadata(item: &TrashItem) -> Result<TrashItemMetadata, Error> {
+pub fn metadata(_ctx: &PlatformTrashContext, item: &TrashItem, _deep: bool) -> Result<TrashItemMetadata, Error> {
     ensure_com_initialized();
     let id_as_wide = to_wide_path(&item.id);
     let parsing_name = PCWSTR(id_as_wide.as_ptr());
@@ -179,10 +199,11 @@ pub fn metadata(item: &TrashItem) -> Result<TrashItemMetadata, Error> {
         let item2: IShellItem2 = item.cast()?;
         TrashItemSize::Bytes(unsafe { item2.GetUInt64(&PKEY_Size)? })
     };
-    Ok(TrashItemMetadata { size })
+    let kind = if is_dir { ExistingKind::Directory } else { ExistingKind::File };
+    Ok(TrashItemMetadata::new(size, kind))
 }
 
-pub fn purge_all<I>(items: I) -> Result<(), Error>
+pub fn purge_all<I>(_ctx: &PlatformTrashContext, items: I) -> Result<(), Error>
 where
     I: IntoIterator,
     <I as IntoIterator>::Item: Borrow<TrashItem>,
@@ -206,7 +227,7 @@ where
     }
 }
 
-pub fn restore_all<I>(items: I) -> Result<(), Error>
+pub fn restore_all<I>(_ctx: &PlatformTrashContext, items: I) -> Result<(), Error>
 where
     I: IntoIterator<Item = TrashItem>,
 {
@@ -246,6 +267,27 @@ where
     }
 }
 
+/// Windows ignores the read-only attribute of folders and access is controlled by ACLs instead,
+/// so any existing folder is assumed to be writable and access is only checked when restoring.
+pub fn is_writable_dir(_path: &std::path::Path) -> bool {
+    true
+}
+
+/// The recycle bin restores all items in a single operation that may stop partway through, and
+/// the items it restored can't be moved back, so restoring can't be transactional.
+pub fn restore_all_transactional(_ctx: &PlatformTrashContext, _items: Vec<TrashItem>) -> Result<(), Error> {
+    Err(unsupported_restore_option("transactional"))
+}
+
+pub(crate) fn check_restore_transactional(_items: &[TrashItem], _taken_paths: &[PathBuf]) -> Result<(), Error> {
+    Err(unsupported_restore_option("transactional"))
+}
+
+/// The error for the options of [`RestoreOptions`](crate::RestoreOptions) the recycle bin doesn't support.
+pub(crate) fn unsupported_restore_option(option: &str) -> Error {
+    Error::Unknown { description: format!("The `{option}` restore option is not supported on Windows") }
+}
+
 unsafe fn get_display_name(psi: &IShellItem, sigdnname: SIGDN) -> Result<OsString, Error> {
     let name = psi.GetDisplayName(sigdnname)?;
     let result = wstr_to_os_string(name);